@@ -0,0 +1,124 @@
+//! How well-conditioned a Fisher matrix is, to judge whether its inverse
+//! (the CRB) can be trusted.
+
+use ndarray::prelude::*;
+use num_complex::*;
+
+use super::{linalg, CalcError, PINV_RCOND};
+
+/// How many of the smallest eigenvalues, and of the antennas most involved in
+/// the weakest direction, to print.
+const NUM_REPORTED: usize = 5;
+
+pub struct Diagnostics {
+    /// Eigenvalues of the Fisher matrix, ascending
+    pub eigenvalues: Array1<f64>,
+
+    /// Ratio of the largest to the smallest eigenvalue magnitude (infinite
+    /// if the matrix is singular)
+    pub condition_number: f64,
+
+    /// Weight `|v_a|^2` of each parameter in the eigenvector of the smallest
+    /// eigenvalue, i.e. which antennas are most poorly constrained
+    pub weakest_direction: Array1<f64>,
+
+    /// Largest element of `F - F^H` relative to the largest element of `F`
+    pub hermitian_error: f64,
+
+    /// Whether every eigenvalue is positive (beyond rounding)
+    pub positive_definite: bool,
+}
+
+/// Work out the eigen-spectrum and sanity checks of a Fisher matrix.
+pub fn diagnose(fisher: &Array2<Complex64>) -> Result<Diagnostics, CalcError> {
+    let scale = fisher.iter().fold(0.0f64, |max, f| max.max(f.norm()));
+    let asymmetry = fisher.indexed_iter().fold(0.0f64, |max, ((p, q), f)| {
+        max.max((f - fisher[[q, p]].conj()).norm())
+    });
+
+    // The eigen-decomposition assumes a Hermitian matrix, so symmetrise
+    // first; any asymmetry has been recorded above.
+    let hermitian = (fisher + &fisher.t().mapv(|f| f.conj())).mapv(|f| f / 2.0);
+    let (eigenvalues, eigenvectors) = linalg::eigh(&hermitian)?;
+
+    let max = eigenvalues.iter().fold(0.0f64, |max, e| max.max(e.abs()));
+    let min = eigenvalues[0];
+    let condition_number = if min.abs() > 0.0 {
+        max / min.abs()
+    } else {
+        f64::INFINITY
+    };
+
+    return Ok(Diagnostics {
+        condition_number,
+        weakest_direction: eigenvectors.column(0).mapv(|v| v.norm_sqr()),
+        hermitian_error: if scale > 0.0 { asymmetry / scale } else { 0.0 },
+        positive_definite: min > PINV_RCOND * max,
+        eigenvalues,
+    });
+}
+
+impl std::fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let n = self.eigenvalues.len();
+        writeln!(
+            f,
+            "Eigenvalues: {:e} to {:e}, condition number {:e}",
+            self.eigenvalues[0],
+            self.eigenvalues[n - 1],
+            self.condition_number
+        )?;
+        writeln!(
+            f,
+            "Smallest eigenvalues: {:?}",
+            self.eigenvalues.slice(s![..NUM_REPORTED.min(n)]).to_vec()
+        )?;
+        writeln!(
+            f,
+            "Hermitian error: {:e}, positive definite: {}",
+            self.hermitian_error, self.positive_definite
+        )?;
+
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| self.weakest_direction[b].total_cmp(&self.weakest_direction[a]));
+        let weakest: Vec<String> = order
+            .iter()
+            .take(NUM_REPORTED)
+            .map(|&p| format!("{} ({:.3})", p, self.weakest_direction[p]))
+            .collect();
+        write!(f, "Most poorly constrained: {}", weakest.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagonal_matrix() {
+        let fisher = Array2::from_diag(&array![4.0, 0.5, 2.0].mapv(|f| Complex64::new(f, 0.0)));
+        let diagnostics = diagnose(&fisher).unwrap();
+        assert_eq!(diagnostics.eigenvalues.to_vec(), vec![0.5, 2.0, 4.0]);
+        assert_eq!(diagnostics.condition_number, 8.0);
+        assert_eq!(diagnostics.weakest_direction.to_vec(), vec![0.0, 1.0, 0.0]);
+        assert_eq!(diagnostics.hermitian_error, 0.0);
+        assert!(diagnostics.positive_definite);
+    }
+
+    #[test]
+    fn singular_and_asymmetric() {
+        // Every row sums to zero, so the constant vector is a null direction.
+        let mut fisher = array![[1.0, -1.0, 0.0], [-1.0, 2.0, -1.0], [0.0, -1.0, 1.0]]
+            .mapv(|f| Complex64::new(f, 0.0));
+        // An anti-Hermitian error is recorded but does not change the spectrum.
+        fisher[[0, 1]] += Complex64::new(0.0, 0.01);
+        fisher[[1, 0]] += Complex64::new(0.0, 0.01);
+        let diagnostics = diagnose(&fisher).unwrap();
+        assert!(!diagnostics.positive_definite);
+        assert!(diagnostics.condition_number > 1e12);
+        assert!((diagnostics.hermitian_error - 0.01).abs() < 1e-12);
+        for w in diagnostics.weakest_direction.iter() {
+            assert!((w - 1.0 / 3.0).abs() < 1e-9, "{w}");
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Direction-dependent gains. The sky is split into clusters (see
+//! [`cluster_components`]) and every antenna has an independent gain
+//! towards each cluster, `V_ab = sum_d g_ad M^d_ab g_bd^*`.
+
+use marlu::RADec;
+use ndarray::prelude::*;
+use num_complex::*;
+
+use super::{add_visibility_information, linalg, model_visibilities, Averaging, CalcError};
+use crate::srclist::*;
+
+/// Build the Fisher information matrix of every antenna's log-amplitude gain
+/// towards every cluster, evaluated at `g = 1`. Parameter
+/// `a * num_directions + d` is the gain of antenna `a` towards `clusters[d]`.
+/// Only baselines in `selection` are used.
+#[allow(clippy::too_many_arguments)]
+pub fn direction_dependent_fisher(
+    baselines_xy: &Array3<f64>,
+    clusters: &[Vec<&Component>],
+    lambda: f64,
+    freq: f64,
+    sigma: f64,
+    phase_centre: RADec,
+    averaging: Averaging,
+    selection: &Array2<bool>,
+) -> Array2<Complex64> {
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let num_dirs = clusters.len();
+    let models: Vec<Array2<Complex64>> = clusters
+        .iter()
+        .map(|cluster| {
+            model_visibilities(baselines_xy, cluster, lambda, freq, phase_centre, averaging)
+        })
+        .collect();
+
+    let mut fisher = Array2::<Complex64>::zeros((num_ants * num_dirs, num_ants * num_dirs));
+    let mut derivatives = Vec::with_capacity(2 * num_dirs);
+    for a in 0..num_ants {
+        for b in (a + 1)..num_ants {
+            if !selection[[a, b]] {
+                continue;
+            }
+            // dV_ab / d(ln|g_ad|) = dV_ab / d(ln|g_bd|) = M^d_ab
+            derivatives.clear();
+            for (d, model) in models.iter().enumerate() {
+                derivatives.push((a * num_dirs + d, model[[a, b]]));
+                derivatives.push((b * num_dirs + d, model[[a, b]]));
+            }
+            add_visibility_information(&mut fisher, &derivatives, sigma);
+        }
+    }
+
+    return fisher;
+}
+
+/// The CRB of every antenna's gain (rows) towards every direction
+/// (columns). There must be at least one direction.
+pub fn direction_crb(
+    fisher: &Array2<Complex64>,
+    num_dirs: usize,
+) -> Result<Array2<f64>, CalcError> {
+    if num_dirs == 0 {
+        return Err(CalcError::NoDirections);
+    }
+    let crb = linalg::inv_hermitian(fisher)?;
+    let num_ants = fisher.nrows() / num_dirs;
+    return Ok(Array2::from_shape_fn((num_ants, num_dirs), |(a, d)| {
+        crb[[a * num_dirs + d, a * num_dirs + d]].re
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc::fisher_matrix;
+
+    #[test]
+    fn no_directions() {
+        let fisher = Array2::<Complex64>::zeros((0, 0));
+        assert!(matches!(
+            direction_crb(&fisher, 0),
+            Err(CalcError::NoDirections)
+        ));
+    }
+
+    #[test]
+    fn one_direction_matches_direction_independent() {
+        let phase_centre = RADec::from_degrees(0.0, -27.0);
+        let sources: Vec<Component> = [(1.0, -26.0, 4.0), (-2.0, -28.0, 1.5)]
+            .iter()
+            .map(|&(ra, dec, i)| Component {
+                radec: RADec::from_degrees(ra, dec),
+                flux_type: FluxDensityType::PowerLaw {
+                    si: 0.0,
+                    fd: FluxDensity {
+                        freq: 150e6,
+                        i,
+                        q: 0.0,
+                        u: 0.0,
+                        v: 0.0,
+                    },
+                },
+            })
+            .collect();
+        let comps: Vec<&Component> = sources.iter().collect();
+        let positions = [(0.0, 0.0), (14.0, 3.0), (-6.0, 22.0), (30.0, -9.0)];
+        let baselines_xy = Array3::from_shape_fn((4, 4, 2), |(a, b, k)| match k {
+            0 => positions[a].0 - positions[b].0,
+            _ => positions[a].1 - positions[b].1,
+        });
+        let averaging = Averaging {
+            channel_width: 0.0,
+            int_time: 0.0,
+        };
+        let selection = Array2::from_elem((4, 4), true);
+
+        let dd = direction_dependent_fisher(
+            &baselines_xy,
+            std::slice::from_ref(&comps),
+            2.0,
+            150e6,
+            0.3,
+            phase_centre,
+            averaging,
+            &selection,
+        );
+        let di = fisher_matrix(
+            &baselines_xy,
+            &comps,
+            2.0,
+            150e6,
+            0.3,
+            phase_centre,
+            averaging,
+            &selection,
+            None,
+        );
+        for (x, y) in dd.iter().zip(di.iter()) {
+            assert!((x - y).norm() < 1e-9 * y.norm().max(1.0), "{dd} != {di}");
+        }
+    }
+}
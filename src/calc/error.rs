@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+/// Errors associated with building or inverting a Fisher matrix.
+#[derive(Error, Debug)]
+pub enum CalcError {
+    #[error(
+        "The Fisher matrix is not positive definite (failed at row {row}); it cannot be inverted. If it is singular, it needs a gauge other than `inverse`"
+    )]
+    NotPositiveDefinite { row: usize },
+
+    #[error("Reference antenna {reference} is out of range; there are only {num_ants} antennas")]
+    ReferenceAntennaOutOfRange { reference: usize, num_ants: usize },
+
+    #[error("The `project` gauge needs known degenerate directions, but there are none for these parameters; use `inverse`, `reference_antenna` or `pseudoinverse`")]
+    NoKnownDegeneracies,
+
+    #[error("{num_groups} groups would each need an ionospheric offset, more than the {max} allowed; use 'ionospheric_offsets: {{clusters: N}}' instead")]
+    TooManyOffsetGroups { num_groups: usize, max: usize },
+
+    #[error("There are no components left to cluster into calibration directions")]
+    NoDirections,
+
+    #[error("The eigen-decomposition of the Fisher matrix did not converge")]
+    EigenNotConverged,
+}
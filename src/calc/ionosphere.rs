@@ -0,0 +1,242 @@
+//! Ionospheric position offsets. Refraction shifts the apparent position of
+//! each source by `(dl, dm)`, and these offsets are estimated jointly with
+//! the antenna gains. Components are grouped (individually, or by cluster)
+//! and every component in a group shares the same offset.
+
+use marlu::RADec;
+use ndarray::prelude::*;
+use num_complex::*;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+use super::{fisher_from_jacobian, linalg, Averaging, CalcError};
+use crate::srclist::*;
+
+/// The most groups that can have an offset. Each adds two parameters to a
+/// dense Fisher matrix that is inverted in every channel.
+pub const MAX_OFFSET_GROUPS: usize = 500;
+
+pub struct IonosphereCrb {
+    /// CRB of each antenna's log-amplitude gain with the offsets known
+    pub gains_fixed: Array1<f64>,
+
+    /// CRB of each antenna's log-amplitude gain when the offsets are
+    /// estimated as well
+    pub gains_joint: Array1<f64>,
+
+    /// CRB of each group's `[dl, dm]` offset
+    pub offsets: Array2<f64>,
+}
+
+/// Build the joint Fisher information matrix of every antenna's
+/// log-amplitude gain (parameters `0..num_ants`) and every group's offset
+/// (`dl` of group `g` is parameter `num_ants + 2 * g`, `dm` the one after),
+/// evaluated at `g = 1` and zero offset. With the unshifted model `M^g_ab` of
+/// group `g`, `dV_ab / d(dl_g) = 2 pi i u M^g_ab` and
+/// `dV_ab / d(dm_g) = 2 pi i v M^g_ab`. Only baselines in `selection` are
+/// used. There can be at most [`MAX_OFFSET_GROUPS`] groups.
+#[allow(clippy::too_many_arguments)]
+pub fn ionosphere_fisher(
+    baselines_xy: &Array3<f64>,
+    groups: &[Vec<&Component>],
+    lambda: f64,
+    freq: f64,
+    sigma: f64,
+    phase_centre: RADec,
+    averaging: Averaging,
+    selection: &Array2<bool>,
+) -> Result<Array2<Complex64>, CalcError> {
+    if groups.len() > MAX_OFFSET_GROUPS {
+        return Err(CalcError::TooManyOffsetGroups {
+            num_groups: groups.len(),
+            max: MAX_OFFSET_GROUPS,
+        });
+    }
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let num_params = num_ants + 2 * groups.len();
+    let baselines = baselines_xy / lambda;
+
+    let comps: Vec<(usize, f64, f64, f64)> = groups
+        .iter()
+        .enumerate()
+        .flat_map(|(g, group)| {
+            group.iter().map(move |comp| {
+                let lmn = comp.radec.to_lmn(phase_centre);
+                (g, comp.estimate_at_freq(freq).i, lmn.l, lmn.m)
+            })
+        })
+        .collect();
+
+    let pairs: Vec<(usize, usize)> = (0..num_ants)
+        .flat_map(|a| ((a + 1)..num_ants).map(move |b| (a, b)))
+        .filter(|&(a, b)| selection[[a, b]])
+        .collect();
+
+    // With one offset per component the parameter count is large, so form
+    // the derivatives of every baseline at once.
+    let mut jacobian = Array2::<Complex64>::zeros((pairs.len(), num_params));
+    jacobian
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .zip(pairs.par_iter())
+        .for_each(|(mut row, &(a, b))| {
+            let (u, v) = (baselines[[a, b, 0]], baselines[[a, b, 1]]);
+            let mut model = Complex64::new(0.0, 0.0);
+            for &(g, flux, l, m) in comps.iter() {
+                let flux = flux * averaging.decorrelation(u, v, l, m, freq, phase_centre.dec);
+                let vis = Complex64::from_polar(flux, 2.0 * PI * (u * l + v * m));
+                model += vis;
+                row[num_ants + 2 * g] += Complex64::new(0.0, 2.0 * PI * u) * vis;
+                row[num_ants + 2 * g + 1] += Complex64::new(0.0, 2.0 * PI * v) * vis;
+            }
+            row[a] = model;
+            row[b] = model;
+        });
+
+    return Ok(fisher_from_jacobian(&jacobian, sigma));
+}
+
+/// The CRB of the gains and offsets from the joint Fisher matrix, along with
+/// the gain CRB if the offsets were known, to show how much estimating the
+/// offsets inflates the gain errors.
+pub fn ionosphere_crb(
+    fisher: &Array2<Complex64>,
+    num_ants: usize,
+) -> Result<IonosphereCrb, CalcError> {
+    let crb = linalg::inv_hermitian(fisher)?;
+    let gains_fixed = linalg::inv_hermitian(&fisher.slice(s![..num_ants, ..num_ants]).to_owned())?;
+
+    let num_groups = (fisher.nrows() - num_ants) / 2;
+    return Ok(IonosphereCrb {
+        gains_fixed: gains_fixed.diag().mapv(|c| c.re),
+        gains_joint: crb.diag().slice(s![..num_ants]).mapv(|c| c.re),
+        offsets: Array2::from_shape_fn((num_groups, 2), |(g, k)| {
+            let p = num_ants + 2 * g + k;
+            crb[[p, p]].re
+        }),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc::fisher_matrix;
+
+    const FREQ: f64 = 150e6;
+
+    fn point(radec: RADec, flux: f64) -> Component {
+        return Component {
+            radec,
+            flux_type: FluxDensityType::PowerLaw {
+                si: 0.0,
+                fd: FluxDensity {
+                    freq: FREQ,
+                    i: flux,
+                    q: 0.0,
+                    u: 0.0,
+                    v: 0.0,
+                },
+            },
+        };
+    }
+
+    const NO_AVERAGING: Averaging = Averaging {
+        channel_width: 0.0,
+        int_time: 0.0,
+    };
+
+    #[test]
+    fn too_many_groups() {
+        let comp = point(RADec::from_degrees(0.0, -27.0), 1.0);
+        let groups = vec![vec![&comp]; MAX_OFFSET_GROUPS + 1];
+        let result = ionosphere_fisher(
+            &Array3::zeros((2, 2, 2)),
+            &groups,
+            2.0,
+            FREQ,
+            1.0,
+            RADec::from_degrees(0.0, -27.0),
+            NO_AVERAGING,
+            &Array2::from_elem((2, 2), true),
+        );
+        assert!(matches!(result, Err(CalcError::TooManyOffsetGroups { .. })));
+    }
+
+    #[test]
+    fn offsets_cost_gain_precision() {
+        let positions = [
+            (0.0, 0.0),
+            (35.0, 4.0),
+            (-12.0, 50.0),
+            (80.0, -30.0),
+            (-60.0, -45.0),
+            (20.0, 110.0),
+        ];
+        let num_ants = positions.len();
+        let baselines_xy = Array3::from_shape_fn((num_ants, num_ants, 2), |(a, b, k)| match k {
+            0 => positions[a].0 - positions[b].0,
+            _ => positions[a].1 - positions[b].1,
+        });
+        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / FREQ;
+        let (sigma, flux) = (0.5, 4.0);
+        let phase_centre = RADec::from_degrees(0.0, -27.0);
+        let sources = [
+            point(RADec::from_degrees(1.0, -26.0), flux),
+            point(RADec::from_degrees(-3.0, -29.0), 2.0),
+        ];
+        let groups = vec![vec![&sources[0]], vec![&sources[1]]];
+        let selection = Array2::from_elem((num_ants, num_ants), true);
+        let fisher = ionosphere_fisher(
+            &baselines_xy,
+            &groups,
+            lambda,
+            FREQ,
+            sigma,
+            phase_centre,
+            NO_AVERAGING,
+            &selection,
+        )
+        .unwrap();
+
+        // The gain block is the gains-only Fisher matrix.
+        let gains = fisher_matrix(
+            &baselines_xy,
+            &[&sources[0], &sources[1]],
+            lambda,
+            FREQ,
+            sigma,
+            phase_centre,
+            NO_AVERAGING,
+            &selection,
+            None,
+        );
+        let block = fisher.slice(s![..num_ants, ..num_ants]);
+        for ((a, b), f) in gains.indexed_iter() {
+            assert!((block[[a, b]] - f).norm() < 1e-9 * f.norm(), "F[{a}, {b}]");
+        }
+
+        // |dV_ab / d(dl)|^2 = (2 pi u B)^2 whatever the source position.
+        let expected: f64 = (0..num_ants)
+            .flat_map(|a| ((a + 1)..num_ants).map(move |b| (a, b)))
+            .map(|(a, b)| {
+                2.0 / sigma.powi(2) * (2.0 * PI * flux * baselines_xy[[a, b, 0]] / lambda).powi(2)
+            })
+            .sum();
+        let f_dl = fisher[[num_ants, num_ants]].re;
+        assert!(
+            (f_dl - expected).abs() < 1e-9 * expected,
+            "{f_dl} != {expected}"
+        );
+
+        let crb = ionosphere_crb(&fisher, num_ants).unwrap();
+        assert_eq!(crb.offsets.dim(), (2, 2));
+        for (joint, fixed) in crb.gains_joint.iter().zip(crb.gains_fixed.iter()) {
+            assert!(joint >= &(fixed * (1.0 - 1e-9)), "{joint} < {fixed}");
+        }
+        assert!(crb
+            .gains_joint
+            .iter()
+            .zip(crb.gains_fixed.iter())
+            .any(|(joint, fixed)| joint > &(fixed * (1.0 + 1e-6))));
+    }
+}
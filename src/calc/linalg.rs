@@ -0,0 +1,558 @@
+//! Small dense linear algebra for Hermitian matrices.
+//!
+//! ndarray-linalg needs a LAPACK backend at link time, which we don't
+//! configure. The Fisher matrices here are at most a few thousand elements
+//! across, so a Cholesky factorisation and a cyclic Jacobi eigensolver are
+//! plenty.
+
+use ndarray::prelude::*;
+use num_complex::*;
+
+use super::error::CalcError;
+
+/// Maximum number of Jacobi sweeps before giving up on convergence.
+const MAX_SWEEPS: usize = 100;
+
+/// A Cholesky pivot smaller than this fraction of the largest diagonal
+/// element means the matrix is singular to working precision.
+const PIVOT_TOLERANCE: f64 = 1e-12;
+
+/// Invert a Hermitian positive-definite matrix via its Cholesky factorisation
+/// `A = L L^H`, so that `A^-1 = L^-H L^-1`.
+pub fn inv_hermitian(a: &Array2<Complex64>) -> Result<Array2<Complex64>, CalcError> {
+    let n = a.nrows();
+    let l = cholesky(a)?;
+
+    // Invert the lower-triangular factor by forward substitution.
+    let mut l_inv = Array2::<Complex64>::zeros((n, n));
+    for j in 0..n {
+        l_inv[[j, j]] = 1.0 / l[[j, j]];
+        for i in (j + 1)..n {
+            let mut sum = Complex64::new(0.0, 0.0);
+            for k in j..i {
+                sum += l[[i, k]] * l_inv[[k, j]];
+            }
+            l_inv[[i, j]] = -sum / l[[i, i]];
+        }
+    }
+
+    let mut inv = Array2::<Complex64>::zeros((n, n));
+    for i in 0..n {
+        for j in 0..=i {
+            let mut sum = Complex64::new(0.0, 0.0);
+            for k in i..n {
+                sum += l_inv[[k, i]].conj() * l_inv[[k, j]];
+            }
+            inv[[i, j]] = sum;
+            inv[[j, i]] = sum.conj();
+        }
+    }
+
+    return Ok(inv);
+}
+
+/// Lower-triangular Cholesky factor of a Hermitian positive-definite matrix.
+/// A matrix that is singular only because of rounding is rejected too,
+/// rather than producing an enormous inverse.
+pub fn cholesky(a: &Array2<Complex64>) -> Result<Array2<Complex64>, CalcError> {
+    let n = a.nrows();
+    let mut l = Array2::<Complex64>::zeros((n, n));
+    let max_diag = a.diag().iter().fold(0.0, |max: f64, x| max.max(x.re));
+
+    for j in 0..n {
+        let mut diag = a[[j, j]].re;
+        for k in 0..j {
+            diag -= l[[j, k]].norm_sqr();
+        }
+        if diag.is_nan() || diag <= 0.0 || diag <= PIVOT_TOLERANCE * max_diag {
+            return Err(CalcError::NotPositiveDefinite { row: j });
+        }
+        let diag = diag.sqrt();
+        l[[j, j]] = Complex64::new(diag, 0.0);
+
+        for i in (j + 1)..n {
+            let mut sum = a[[i, j]];
+            for k in 0..j {
+                sum -= l[[i, k]] * l[[j, k]].conj();
+            }
+            l[[i, j]] = sum / diag;
+        }
+    }
+
+    return Ok(l);
+}
+
+/// Eigen-decomposition of a Hermitian matrix.
+///
+/// Returns the (real) eigenvalues in ascending order and the matrix whose
+/// columns are the corresponding eigenvectors. Fisher matrices of real
+/// parameters are real, and go through the much faster real-symmetric
+/// solver; anything genuinely complex uses the cyclic Jacobi method.
+pub fn eigh(a: &Array2<Complex64>) -> Result<(Array1<f64>, Array2<Complex64>), CalcError> {
+    if a.iter().all(|x| x.im == 0.0) {
+        let (eigenvalues, eigenvectors) = eigh_real(&a.mapv(|x| x.re))?;
+        return Ok((eigenvalues, eigenvectors.mapv(|x| Complex64::new(x, 0.0))));
+    }
+    return eigh_jacobi(a);
+}
+
+/// Eigen-decomposition of a Hermitian matrix with the cyclic Jacobi method.
+fn eigh_jacobi(a: &Array2<Complex64>) -> Result<(Array1<f64>, Array2<Complex64>), CalcError> {
+    let n = a.nrows();
+    let mut a = a.to_owned();
+    let mut v = Array2::<Complex64>::eye(n);
+
+    let scale: f64 = a.iter().map(|x| x.norm_sqr()).sum::<f64>().sqrt();
+    let mut converged = scale == 0.0;
+
+    for _ in 0..MAX_SWEEPS {
+        if converged {
+            break;
+        }
+
+        let mut off: f64 = 0.0;
+        for p in 0..n {
+            for q in (p + 1)..n {
+                off += a[[p, q]].norm_sqr();
+            }
+        }
+        if off.sqrt() <= f64::EPSILON * scale {
+            converged = true;
+            break;
+        }
+
+        for p in 0..n {
+            for q in (p + 1)..n {
+                let apq = a[[p, q]];
+                let abs_apq = apq.norm();
+                if abs_apq <= f64::MIN_POSITIVE {
+                    continue;
+                }
+
+                // Rotate the phase out of a_pq, then do a real Jacobi rotation.
+                let phase = apq / abs_apq;
+                let tau = (a[[q, q]].re - a[[p, p]].re) / (2.0 * abs_apq);
+                let t = tau.signum() / (tau.abs() + (1.0 + tau * tau).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = t * c;
+
+                let u_pp = Complex64::new(c, 0.0);
+                let u_pq = Complex64::new(s, 0.0);
+                let u_qp = -s * phase.conj();
+                let u_qq = c * phase.conj();
+
+                // A <- A U
+                for k in 0..n {
+                    let akp = a[[k, p]];
+                    let akq = a[[k, q]];
+                    a[[k, p]] = akp * u_pp + akq * u_qp;
+                    a[[k, q]] = akp * u_pq + akq * u_qq;
+                }
+                // A <- U^H A
+                for k in 0..n {
+                    let apk = a[[p, k]];
+                    let aqk = a[[q, k]];
+                    a[[p, k]] = u_pp.conj() * apk + u_qp.conj() * aqk;
+                    a[[q, k]] = u_pq.conj() * apk + u_qq.conj() * aqk;
+                }
+                // V <- V U
+                for k in 0..n {
+                    let vkp = v[[k, p]];
+                    let vkq = v[[k, q]];
+                    v[[k, p]] = vkp * u_pp + vkq * u_qp;
+                    v[[k, q]] = vkp * u_pq + vkq * u_qq;
+                }
+
+                a[[p, q]] = Complex64::new(0.0, 0.0);
+                a[[q, p]] = Complex64::new(0.0, 0.0);
+            }
+        }
+    }
+
+    if !converged {
+        return Err(CalcError::EigenNotConverged);
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[[i, i]].re.total_cmp(&a[[j, j]].re));
+
+    let eigenvalues = Array1::from_iter(order.iter().map(|&i| a[[i, i]].re));
+    let eigenvectors = v.select(Axis(1), &order);
+
+    return Ok((eigenvalues, eigenvectors));
+}
+
+/// Eigen-decomposition of a real symmetric matrix: Householder reduction to
+/// tridiagonal form followed by the implicit QL algorithm (the EISPACK
+/// `tred2` and `tql2` routines, as in JAMA).
+fn eigh_real(a: &Array2<f64>) -> Result<(Array1<f64>, Array2<f64>), CalcError> {
+    let n = a.nrows();
+    if n == 0 {
+        return Ok((Array1::zeros(0), Array2::zeros((0, 0))));
+    }
+    // The input is symmetric, so this is also its transpose.
+    let mut v = a.to_owned();
+    let mut d = vec![0.0; n];
+    let mut e = vec![0.0; n];
+
+    // Householder reduction to tridiagonal form.
+    for j in 0..n {
+        d[j] = v[[j, n - 1]];
+    }
+    for i in (1..n).rev() {
+        let mut scale = 0.0;
+        let mut h = 0.0;
+        for d_k in d.iter().take(i) {
+            scale += d_k.abs();
+        }
+        if scale == 0.0 {
+            e[i] = d[i - 1];
+            for j in 0..i {
+                d[j] = v[[j, i - 1]];
+                v[[j, i]] = 0.0;
+                v[[i, j]] = 0.0;
+            }
+        } else {
+            for d_k in d.iter_mut().take(i) {
+                *d_k /= scale;
+                h += *d_k * *d_k;
+            }
+            let f = d[i - 1];
+            let mut g = h.sqrt();
+            if f > 0.0 {
+                g = -g;
+            }
+            e[i] = scale * g;
+            h -= f * g;
+            d[i - 1] = f - g;
+            for e_j in e.iter_mut().take(i) {
+                *e_j = 0.0;
+            }
+
+            for j in 0..i {
+                let f = d[j];
+                v[[i, j]] = f;
+                let mut g = e[j] + v[[j, j]] * f;
+                let row = v.row(j);
+                let row = &row.as_slice().unwrap()[(j + 1)..i];
+                for ((v_jk, d_k), e_k) in row.iter().zip(&d[(j + 1)..i]).zip(&mut e[(j + 1)..i]) {
+                    g += v_jk * d_k;
+                    *e_k += v_jk * f;
+                }
+                e[j] = g;
+            }
+            let mut f = 0.0;
+            for j in 0..i {
+                e[j] /= h;
+                f += e[j] * d[j];
+            }
+            let hh = f / (h + h);
+            for j in 0..i {
+                e[j] -= hh * d[j];
+            }
+            for j in 0..i {
+                let f = d[j];
+                let g = e[j];
+                let mut row = v.row_mut(j);
+                let row = &mut row.as_slice_mut().unwrap()[j..i];
+                for ((v_jk, e_k), d_k) in row.iter_mut().zip(&e[j..i]).zip(&d[j..i]) {
+                    *v_jk -= f * e_k + g * d_k;
+                }
+                d[j] = v[[j, i - 1]];
+                v[[j, i]] = 0.0;
+            }
+        }
+        d[i] = h;
+    }
+
+    // Accumulate the transformations.
+    for i in 0..(n - 1) {
+        v[[i, n - 1]] = v[[i, i]];
+        v[[i, i]] = 1.0;
+        let h = d[i + 1];
+        if h != 0.0 {
+            for k in 0..=i {
+                d[k] = v[[i + 1, k]] / h;
+            }
+            for j in 0..=i {
+                let (row_i1, mut row_j) = v.multi_slice_mut((s![i + 1, ..=i], s![j, ..=i]));
+                let g: f64 = row_i1.iter().zip(row_j.iter()).map(|(x, y)| x * y).sum();
+                for (v_jk, d_k) in row_j.iter_mut().zip(&d[..=i]) {
+                    *v_jk -= g * d_k;
+                }
+            }
+        }
+        for k in 0..=i {
+            v[[i + 1, k]] = 0.0;
+        }
+    }
+    for j in 0..n {
+        d[j] = v[[j, n - 1]];
+        v[[j, n - 1]] = 0.0;
+    }
+    v[[n - 1, n - 1]] = 1.0;
+    e[0] = 0.0;
+
+    // `v` holds the transpose of the eigenvectors, so that rotating pairs of
+    // them below (and the column walks above) go through contiguous memory.
+    let mut w = v;
+
+    // Implicit QL iterations on the tridiagonal matrix.
+    for i in 1..n {
+        e[i - 1] = e[i];
+    }
+    e[n - 1] = 0.0;
+
+    let mut f = 0.0;
+    let mut tst1: f64 = 0.0;
+    for l in 0..n {
+        tst1 = tst1.max(d[l].abs() + e[l].abs());
+        let mut m = l;
+        while m < n {
+            if e[m].abs() <= f64::EPSILON * tst1 {
+                break;
+            }
+            m += 1;
+        }
+
+        if m > l {
+            let mut iterations = 0;
+            loop {
+                iterations += 1;
+                if iterations > 30 * n {
+                    return Err(CalcError::EigenNotConverged);
+                }
+
+                let mut g = d[l];
+                let mut p = (d[l + 1] - g) / (2.0 * e[l]);
+                let mut r = p.hypot(1.0);
+                if p < 0.0 {
+                    r = -r;
+                }
+                d[l] = e[l] / (p + r);
+                d[l + 1] = e[l] * (p + r);
+                let dl1 = d[l + 1];
+                let mut h = g - d[l];
+                for d_i in d.iter_mut().skip(l + 2) {
+                    *d_i -= h;
+                }
+                f += h;
+
+                p = d[m];
+                let mut c = 1.0;
+                let mut c2 = c;
+                let mut c3 = c;
+                let el1 = e[l + 1];
+                let mut s = 0.0;
+                let mut s2 = 0.0;
+                for i in (l..m).rev() {
+                    c3 = c2;
+                    c2 = c;
+                    s2 = s;
+                    g = c * e[i];
+                    h = c * p;
+                    r = p.hypot(e[i]);
+                    e[i + 1] = s * r;
+                    s = e[i] / r;
+                    c = p / r;
+                    p = c * d[i] - s * g;
+                    d[i + 1] = h + s * (c * g + s * d[i]);
+
+                    let (mut w_i, mut w_i1) = w.multi_slice_mut((s![i, ..], s![i + 1, ..]));
+                    for (x, y) in w_i.iter_mut().zip(w_i1.iter_mut()) {
+                        let h = *y;
+                        *y = s * *x + c * h;
+                        *x = c * *x - s * h;
+                    }
+                }
+                p = -s * s2 * c3 * el1 * e[l] / dl1;
+                e[l] = s * p;
+                d[l] = c * p;
+
+                if e[l].abs() <= f64::EPSILON * tst1 {
+                    break;
+                }
+            }
+        }
+        d[l] += f;
+        e[l] = 0.0;
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| d[i].total_cmp(&d[j]));
+
+    let eigenvalues = Array1::from_iter(order.iter().map(|&i| d[i]));
+    let eigenvectors = w.select(Axis(0), &order).reversed_axes();
+
+    return Ok((eigenvalues, eigenvectors));
+}
+
+/// Moore-Penrose pseudo-inverse of a Hermitian matrix. Eigenvalues smaller
+/// than `rcond` times the largest are treated as zero; the eigenvectors of
+/// these discarded directions are returned (as columns) alongside the
+/// inverse.
+pub fn pinv_hermitian(
+    a: &Array2<Complex64>,
+    rcond: f64,
+) -> Result<(Array2<Complex64>, Array2<Complex64>), CalcError> {
+    let (eigenvalues, eigenvectors) = eigh(a)?;
+
+    // A^+ = V diag(1 / e) V^H, skipping the (near-)null directions.
+    let max = eigenvalues.iter().fold(0.0f64, |acc, e| acc.max(e.abs()));
+    let mut scaled = eigenvectors.clone();
+    let mut discarded = vec![];
+    for (k, (mut column, &e)) in scaled
+        .axis_iter_mut(Axis(1))
+        .zip(eigenvalues.iter())
+        .enumerate()
+    {
+        if e.abs() <= rcond * max {
+            discarded.push(k);
+            column.fill(Complex64::new(0.0, 0.0));
+        } else {
+            column.mapv_inplace(|v| v / e);
+        }
+    }
+    let inv = scaled.dot(&eigenvectors.t().mapv(|v| v.conj()));
+    let null_space = eigenvectors.select(Axis(1), &discarded);
+
+    return Ok((inv, null_space));
+}
+
+/// Invert a Hermitian matrix that is positive definite apart from a known
+/// null space, spanned by the orthonormal columns of `null_space`. This is
+/// the inverse on the complement of the null space, found as
+/// `(A + s Q Q^H)^-1 - Q Q^H / s`, with `s` on the scale of `A` so that the
+/// sum is well conditioned.
+pub fn inv_projected(
+    a: &Array2<Complex64>,
+    null_space: &Array2<Complex64>,
+) -> Result<Array2<Complex64>, CalcError> {
+    let scale = a.diag().iter().fold(0.0, |max: f64, x| max.max(x.re));
+    let scale = if scale > 0.0 { scale } else { 1.0 };
+    let projector = null_space.dot(&null_space.t().mapv(|v| v.conj()));
+    return Ok(inv_hermitian(&(a + &(&projector * scale)))? - &(projector / scale));
+}
+
+/// Orthonormalise the columns of `vectors` with modified Gram-Schmidt.
+pub fn orthonormalise(mut vectors: Array2<Complex64>) -> Array2<Complex64> {
+    for k in 0..vectors.ncols() {
+        for j in 0..k {
+            let column_j = vectors.column(j).to_owned();
+            let projection: Complex64 = column_j
+                .iter()
+                .zip(vectors.column(k).iter())
+                .map(|(q, v)| q.conj() * v)
+                .sum();
+            vectors.column_mut(k).scaled_add(-projection, &column_j);
+        }
+        let norm = vectors.column(k).mapv(|v| v.norm_sqr()).sum().sqrt();
+        vectors.column_mut(k).mapv_inplace(|v| v / norm);
+    }
+    return vectors;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn c(re: f64, im: f64) -> Complex64 {
+        return Complex64::new(re, im);
+    }
+
+    fn assert_close(a: &Array2<Complex64>, b: &Array2<Complex64>) {
+        assert_eq!(a.dim(), b.dim());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).norm() < 1e-10, "{a} != {b}");
+        }
+    }
+
+    /// A complex Hermitian, positive-definite matrix.
+    fn hermitian() -> Array2<Complex64> {
+        return array![
+            [c(4.0, 0.0), c(1.0, 1.0), c(0.0, 0.0)],
+            [c(1.0, -1.0), c(3.0, 0.0), c(0.0, 0.5)],
+            [c(0.0, 0.0), c(0.0, -0.5), c(2.0, 0.0)],
+        ];
+    }
+
+    /// The 3-antenna graph Laplacian, whose null space is `[1, 1, 1]` and
+    /// whose other eigenvalues are both 3.
+    fn laplacian() -> Array2<Complex64> {
+        return array![[2.0, -1.0, -1.0], [-1.0, 2.0, -1.0], [-1.0, -1.0, 2.0]].mapv(|x| c(x, 0.0));
+    }
+
+    fn reconstruct(
+        eigenvalues: &Array1<f64>,
+        eigenvectors: &Array2<Complex64>,
+    ) -> Array2<Complex64> {
+        let scaled = eigenvectors * &eigenvalues.mapv(|e| c(e, 0.0));
+        return scaled.dot(&eigenvectors.t().mapv(|v| v.conj()));
+    }
+
+    #[test]
+    fn inverse() {
+        let a = hermitian();
+        let inv = inv_hermitian(&a).unwrap();
+        assert_close(&a.dot(&inv), &Array2::eye(3));
+    }
+
+    #[test]
+    fn cholesky_rejects_singular() {
+        assert!(cholesky(&laplacian()).is_err());
+        let l = cholesky(&hermitian()).unwrap();
+        assert_close(&l.dot(&l.t().mapv(|v| v.conj())), &hermitian());
+    }
+
+    #[test]
+    fn real_eigen_decomposition() {
+        let a = array![[2.0, 1.0], [1.0, 2.0]].mapv(|x| c(x, 0.0));
+        let (eigenvalues, eigenvectors) = eigh(&a).unwrap();
+        assert!((eigenvalues[0] - 1.0).abs() < 1e-12);
+        assert!((eigenvalues[1] - 3.0).abs() < 1e-12);
+        assert_close(&reconstruct(&eigenvalues, &eigenvectors), &a);
+
+        let (eigenvalues, eigenvectors) = eigh(&laplacian()).unwrap();
+        assert_close(&reconstruct(&eigenvalues, &eigenvectors), &laplacian());
+    }
+
+    #[test]
+    fn complex_eigen_decomposition() {
+        let a = hermitian();
+        let (eigenvalues, eigenvectors) = eigh(&a).unwrap();
+        assert!(eigenvalues.windows(2).into_iter().all(|w| w[0] <= w[1]));
+        assert_close(&reconstruct(&eigenvalues, &eigenvectors), &a);
+        assert_close(
+            &eigenvectors.t().mapv(|v| v.conj()).dot(&eigenvectors),
+            &Array2::eye(3),
+        );
+    }
+
+    #[test]
+    fn pseudoinverse_of_rank_deficient() {
+        let (inv, null_space) = pinv_hermitian(&laplacian(), 1e-10).unwrap();
+        assert_close(&inv, &(laplacian() / c(9.0, 0.0)));
+        assert_eq!(null_space.ncols(), 1);
+        let overlap = null_space.column(0).sum().norm() / 3.0f64.sqrt();
+        assert!((overlap - 1.0).abs() < 1e-10);
+
+        // Rank one: only `[1, 1, 1]` survives.
+        let (inv, null_space) = pinv_hermitian(&Array2::ones((3, 3)), 1e-10).unwrap();
+        assert_close(&inv, &(Array2::ones((3, 3)) / c(9.0, 0.0)));
+        assert_eq!(null_space.ncols(), 2);
+    }
+
+    #[test]
+    fn projected_inverse() {
+        let null_space = orthonormalise(Array2::ones((3, 1)));
+        let inv = inv_projected(&laplacian(), &null_space).unwrap();
+        assert_close(&inv, &(laplacian() / c(9.0, 0.0)));
+    }
+
+    #[test]
+    fn orthonormal_columns() {
+        let vectors = array![[1.0, 1.0], [1.0, 0.0], [0.0, 2.0]].mapv(|x| c(x, 0.0));
+        let q = orthonormalise(vectors);
+        assert_close(&q.t().mapv(|v| v.conj()).dot(&q), &Array2::eye(2));
+    }
+}
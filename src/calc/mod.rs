@@ -1,62 +1,593 @@
+pub mod diagnostics;
+pub mod direction;
+mod error;
+pub mod ionosphere;
+pub(crate) mod linalg;
+pub mod polarisation;
+pub mod redundant;
+pub mod sky;
+pub mod spectral;
+
+pub use error::*;
+
+use crate::config::{Gauge, LengthUnits, Telescope, UvRange};
+use crate::logging;
 use crate::srclist::*;
-use marlu::RADec;
+use marlu::{constants::SOLAR2SIDEREAL, RADec};
+use mwalib::MetafitsContext;
 use ndarray::prelude::*;
-use ndarray_linalg::*;
 use num_complex::*;
 use rayon::prelude::*;
-use std::f64::consts::PI;
+use std::f64::consts::{PI, TAU};
 
-pub fn calculate_crb(
-    baselines_xy: &Array<f64, Dim<[usize; 3]>>,
-    source_list: ComponentList,
+/// Angular rotation rate of the Earth [rad/s]
+const EARTH_ROTATION_RATE: f64 = TAU / 86400.0 * SOLAR2SIDEREAL;
+
+/// Eigenvalues of a singular Fisher matrix smaller than this fraction of the
+/// largest are treated as degenerate when pseudo-inverting.
+const PINV_RCOND: f64 = 1e-9;
+
+/// How much the visibilities are averaged in frequency and time. Averaging
+/// smears the phase of off-axis sources, reducing their amplitude on long
+/// baselines.
+#[derive(Clone, Copy, Debug)]
+pub struct Averaging {
+    /// Channel width [Hz]
+    pub channel_width: f64,
+
+    /// Integration time [s]
+    pub int_time: f64,
+}
+
+impl Averaging {
+    /// The amplitude decorrelation of a source at (`l`, `m`) on a baseline
+    /// of (`u`, `v`) wavelengths. Bandwidth smearing scales the phase
+    /// `2 pi (ul + vm)` by `channel_width / freq` across the channel; time
+    /// smearing comes from the uv plane rotating about the pole at
+    /// `sin(dec) * EARTH_ROTATION_RATE` (ignoring w). Averaging the phase
+    /// uniformly over either range gives a sinc.
+    pub fn decorrelation(&self, u: f64, v: f64, l: f64, m: f64, freq: f64, dec: f64) -> f64 {
+        let bandwidth = PI * (u * l + v * m) * self.channel_width / freq;
+        let time = PI * EARTH_ROTATION_RATE * self.int_time * dec.sin() * (u * m - v * l);
+        return sinc(bandwidth) * sinc(time);
+    }
+}
+
+impl UvRange {
+    /// Which baselines `(a, b)` are in range at wavelength `lambda`.
+    pub fn selection(&self, baselines_xy: &Array3<f64>, lambda: f64) -> Array2<bool> {
+        let scale = match self.units {
+            LengthUnits::Metres => 1.0,
+            LengthUnits::Wavelengths => 1.0 / lambda,
+        };
+        let num_ants = baselines_xy.len_of(Axis(0));
+        return Array2::from_shape_fn((num_ants, num_ants), |(a, b)| {
+            let length = scale * baselines_xy[[a, b, 0]].hypot(baselines_xy[[a, b, 1]]);
+            return self.min.is_none_or(|min| length >= min)
+                && self.max.is_none_or(|max| length <= max);
+        });
+    }
+}
+
+/// The (east, north) separation of every pair of antennas in the metafits
+/// [m], indexed `[a, b, axis]`.
+pub fn create_baselines(metafits: &MetafitsContext) -> Array3<f64> {
+    let num_ants = metafits.antennas.len();
+    let mut baselines_xy = Array::<f64, _>::zeros((num_ants, num_ants, 2));
+
+    for (i, ant_i) in metafits.antennas.iter().enumerate() {
+        for (j, ant_j) in metafits.antennas.iter().enumerate() {
+            baselines_xy[[i, j, 0]] = ant_i.east_m - ant_j.east_m;
+            baselines_xy[[i, j, 1]] = ant_i.north_m - ant_j.north_m;
+        }
+    }
+    return baselines_xy;
+}
+
+/// The noise on the real part of a single visibility [Jy].
+pub fn calc_rms(t_sys: f64, bandwith: f64, int_time: f64, telescope: Telescope) -> f64 {
+    let a_eff = telescope.effective_area();
+    let k = physical_constants::BOLTZMANN_CONSTANT;
+
+    return 10.0f64.powi(26) * (2.0 * k * t_sys) / (a_eff * (bandwith * int_time).sqrt());
+}
+
+/// The number of cross-correlation baselines `a < b` that are selected.
+pub fn num_selected_baselines(selection: &Array2<bool>) -> usize {
+    return selection
+        .indexed_iter()
+        .filter(|((a, b), &selected)| a < b && selected)
+        .count();
+}
+
+/// Add the Fisher information of one complex visibility with noise
+/// `sigma` (i.e. `E|n|^2 = sigma^2`), given its derivative with respect to
+/// every real parameter it depends on:
+/// `F_pq += 2 / sigma^2 Re(conj(dV/dp) dV/dq)`.
+pub fn add_visibility_information(
+    fisher: &mut Array2<Complex64>,
+    derivatives: &[(usize, Complex64)],
+    sigma: f64,
+) {
+    let weight = 2.0 / sigma.powi(2);
+    for &(p, d_p) in derivatives {
+        for &(q, d_q) in derivatives {
+            fisher[[p, q]] += weight * (d_p.conj() * d_q).re;
+        }
+    }
+}
+
+/// The Fisher information matrix `F = 2 / sigma^2 Re(J^H J)` of real
+/// parameters from the Jacobian `J` of the visibilities (rows) with respect
+/// to every parameter (columns). This is equivalent to
+/// [`add_visibility_information`] for every row, but much faster when each
+/// visibility depends on many parameters.
+pub fn fisher_from_jacobian(jacobian: &Array2<Complex64>, sigma: f64) -> Array2<Complex64> {
+    let jacobian_re = jacobian.mapv(|d| d.re);
+    let jacobian_im = jacobian.mapv(|d| d.im);
+    let information = jacobian_re.t().dot(&jacobian_re) + jacobian_im.t().dot(&jacobian_im);
+
+    return information.mapv(|f| Complex64::new(2.0 / sigma.powi(2) * f, 0.0));
+}
+
+/// Unnormalised sinc, sin(x) / x.
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-8 {
+        return 1.0;
+    }
+    return x.sin() / x;
+}
+
+/// Build the Fisher information matrix of the antenna log-amplitude gains for
+/// a single frequency, using only the baselines in `selection`. Each
+/// component's flux density is reduced by its bandwidth and time
+/// decorrelation on every baseline.
+///
+/// Baseline `(a, b)` has model `M_ab = sum_i B_i exp(2 pi i (u l_i + v m_i))`
+/// and contributes `|M_ab|^2` to `F_ab` and to both `F_aa` and `F_bb`, so each
+/// diagonal element is the sum over that antenna's baselines. If
+/// `autocorrelation_noise` is given, each antenna's autocorrelation (the
+/// real, total flux `M_aa`) is also used as a measurement with that noise,
+/// as long as `selection` includes it.
+///
+/// `|M_ab|^2` is the double sum over component pairs
+/// `sum_ij B_i B_j exp(2 pi i b . (l_i - l_j))`, but forming `M_ab` first
+/// needs only one sum, so the cost is linear in the number of components.
+#[allow(clippy::too_many_arguments)]
+pub fn fisher_matrix(
+    baselines_xy: &Array3<f64>,
+    source_list: &[&Component],
     lambda: f64,
     freq: f64,
     sigma: f64,
     phase_centre: RADec,
-) {
-    let i = Complex::new(0.0, 1.0);
-    let mut crb = Array::<Complex64, _>::zeros((128, 128));
+    averaging: Averaging,
+    selection: &Array2<bool>,
+    autocorrelation_noise: Option<f64>,
+) -> Array2<Complex64> {
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let baselines = baselines_xy / lambda;
+    let comps = flux_and_lm(source_list, freq, phase_centre);
+
+    let mut fisher = Array::<Complex64, _>::zeros((num_ants, num_ants));
+    let progress = logging::progress_bar(num_ants, "antennas");
+    fisher
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(a, mut row)| {
+            for b in (a + 1)..num_ants {
+                if !selection[[a, b]] {
+                    continue;
+                }
+                let (u, v) = (baselines[[a, b, 0]], baselines[[a, b, 1]]);
+                let model = baseline_model(u, v, &comps, freq, phase_centre.dec, averaging);
+                row[b] = Complex64::new(model.norm_sqr(), 0.0);
+            }
+            progress.inc(1);
+        });
+    progress.finish_and_clear();
+
+    for a in 0..num_ants {
+        for b in (a + 1)..num_ants {
+            fisher[[b, a]] = fisher[[a, b]].conj();
+        }
+    }
+
+    // Every baseline of an antenna carries information about its gain.
+    for a in 0..num_ants {
+        fisher[[a, a]] = fisher.row(a).sum();
+    }
+    fisher *= Complex::new(2.0 / sigma.powi(2), 0.0);
+
+    // V_aa = |g_a|^2 M_aa is real, so with real noise sigma_auto it adds
+    // (2 M_aa)^2 / sigma_auto^2.
+    if let Some(sigma_auto) = autocorrelation_noise {
+        let total_flux: f64 = comps.iter().map(|&(flux, _, _)| flux).sum();
+        for a in (0..num_ants).filter(|&a| selection[[a, a]]) {
+            fisher[[a, a]] += (2.0 * total_flux / sigma_auto).powi(2);
+        }
+    }
+
+    return fisher;
+}
 
+/// Calculate the noiseless model visibilities for every antenna pair, i.e.
+/// `M_ab = sum_i B_i D_i exp(2 pi i b_ab . l_i)`, where `D_i` is the
+/// component's decorrelation on that baseline.
+pub fn model_visibilities(
+    baselines_xy: &Array3<f64>,
+    source_list: &[&Component],
+    lambda: f64,
+    freq: f64,
+    phase_centre: RADec,
+    averaging: Averaging,
+) -> Array2<Complex64> {
+    let num_ants = baselines_xy.len_of(Axis(0));
     let baselines = baselines_xy / lambda;
+    let comps = flux_and_lm(source_list, freq, phase_centre);
 
-    for a in 0usize..128usize {
-        println!("{}", a);
-        // (0usize..128usize).into_par_iter().for_each(|a| {
-        for b in a..128usize {
-            for src_i in source_list.iter() {
-                for src_j in source_list.iter() {
-                    // TODO: Need to move these calculations outside
-                    let B_i = src_i.estimate_at_freq(freq).i;
-                    let B_j = src_j.estimate_at_freq(freq).i;
-                    let lmn_i = src_i.radec.to_lmn(phase_centre);
-                    let lmn_j = src_j.radec.to_lmn(phase_centre);
-
-                    let l_i = lmn_i.l;
-                    let m_i = lmn_i.m;
-                    let l_j = lmn_j.l;
-                    let m_j = lmn_j.m;
-                    let complex_part: Complex64 = (2.0
-                        * PI
-                        * i
-                        * (baselines[[a, b, 0]] * (l_i - l_j)
-                            + baselines[[a, b, 1]] * (m_i - m_j)))
-                        .exp();
-
-                    crb[[a, b]] = B_i * B_j * complex_part;
-
-                    if a == b {
-                        crb[[a, b]] += 131.0 * B_i * B_j;
-                    }
+    let mut model = Array2::<Complex64>::zeros((num_ants, num_ants));
+    model
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(a, mut row)| {
+            for (b, vis) in row.iter_mut().enumerate() {
+                let (u, v) = (baselines[[a, b, 0]], baselines[[a, b, 1]]);
+                *vis = baseline_model(u, v, &comps, freq, phase_centre.dec, averaging);
+            }
+        });
+
+    return model;
+}
+
+/// The Stokes I flux density at `freq` [Jy] and direction cosines `(l, m)`
+/// of every component, which don't depend on the baseline.
+fn flux_and_lm(source_list: &[&Component], freq: f64, phase_centre: RADec) -> Vec<(f64, f64, f64)> {
+    return source_list
+        .iter()
+        .map(|comp| {
+            let lmn = comp.radec.to_lmn(phase_centre);
+            (comp.estimate_at_freq(freq).i, lmn.l, lmn.m)
+        })
+        .collect();
+}
+
+/// The model visibility `sum_i B_i D_i exp(2 pi i (u l_i + v m_i))` of a
+/// single baseline `(u, v)` [wavelengths], from [`flux_and_lm`].
+fn baseline_model(
+    u: f64,
+    v: f64,
+    comps: &[(f64, f64, f64)],
+    freq: f64,
+    dec: f64,
+    averaging: Averaging,
+) -> Complex64 {
+    return comps
+        .iter()
+        .map(|&(flux, l, m)| {
+            let flux = flux * averaging.decorrelation(u, v, l, m, freq, dec);
+            Complex64::from_polar(flux, 2.0 * PI * (u * l + v * m))
+        })
+        .sum();
+}
+
+/// The Cramer-Rao bound, along with the directions in parameter space that
+/// the chosen gauge left unconstrained.
+pub struct Crb {
+    /// The covariance bound; the diagonal is the minimum variance of each
+    /// antenna's gain
+    pub matrix: Array2<Complex64>,
+
+    /// Unit vectors (columns) spanning the discarded null space. The bound
+    /// has no variance along these directions.
+    pub null_space: Array2<Complex64>,
+}
+
+/// The Fisher information matrix of the gain phases, at `g = 1`, from the
+/// (log-amplitude) matrix of [`fisher_matrix`]. Since `dV_ab / dphi_a =
+/// i M_ab` and `dV_ab / dphi_b = -i M_ab`, the baseline terms are the same but
+/// the off-diagonal elements change sign. Autocorrelations carry no phase
+/// information, so the diagonal is rebuilt from the off-diagonal elements.
+/// The overall phase is unconstrained, so this matrix is always singular.
+pub fn phase_fisher(fisher: &Array2<Complex64>) -> Array2<Complex64> {
+    let num_ants = fisher.nrows();
+    let mut phase = -fisher;
+    for a in 0..num_ants {
+        phase[[a, a]] = Complex64::new(0.0, 0.0);
+        phase[[a, a]] = -phase.row(a).sum();
+    }
+    return phase;
+}
+
+/// Calculate the Cramer-Rao bound on the antenna gains, i.e. the inverse of
+/// the Fisher matrix, fixing any degeneracies with `gauge`. `degenerate`
+/// holds the known degenerate directions (columns) projected out by
+/// [`Gauge::Project`], which fails if there are none; it is not used
+/// otherwise.
+pub fn calculate_crb(
+    fisher: &Array2<Complex64>,
+    gauge: &Gauge,
+    degenerate: &Array2<Complex64>,
+) -> Result<Crb, CalcError> {
+    let num_params = fisher.nrows();
+
+    match gauge {
+        Gauge::Inverse => {
+            return Ok(Crb {
+                matrix: linalg::inv_hermitian(fisher)?,
+                null_space: Array2::zeros((num_params, 0)),
+            });
+        }
+
+        // Hold the reference antenna's gain fixed by dropping its row and
+        // column before inverting.
+        Gauge::ReferenceAntenna(reference) => {
+            if *reference >= num_params {
+                return Err(CalcError::ReferenceAntennaOutOfRange {
+                    reference: *reference,
+                    num_ants: num_params,
+                });
+            }
+            let kept: Vec<usize> = (0..num_params).filter(|p| p != reference).collect();
+            let reduced = fisher.select(Axis(0), &kept).select(Axis(1), &kept);
+            let reduced_crb = linalg::inv_hermitian(&reduced)?;
+
+            let mut matrix = Array2::<Complex64>::zeros((num_params, num_params));
+            for (i, &p) in kept.iter().enumerate() {
+                for (j, &q) in kept.iter().enumerate() {
+                    matrix[[p, q]] = reduced_crb[[i, j]];
                 }
             }
+            let mut null_space = Array2::<Complex64>::zeros((num_params, 1));
+            null_space[[*reference, 0]] = Complex64::new(1.0, 0.0);
+
+            return Ok(Crb { matrix, null_space });
+        }
+
+        Gauge::Project => {
+            if degenerate.ncols() == 0 {
+                return Err(CalcError::NoKnownDegeneracies);
+            }
+            let null_space = linalg::orthonormalise(degenerate.clone());
+            return Ok(Crb {
+                matrix: linalg::inv_projected(fisher, &null_space)?,
+                null_space,
+            });
+        }
+
+        Gauge::Pseudoinverse => {
+            let (matrix, null_space) = linalg::pinv_hermitian(fisher, PINV_RCOND)?;
+            return Ok(Crb { matrix, null_space });
         }
     }
+}
 
-    for a in 0usize..128usize {
-        for b in a..128usize {
-            crb[[b, a]] = crb[[a, b]].conj();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQ: f64 = 150e6;
+
+    fn point(radec: RADec, flux: f64) -> Component {
+        return Component {
+            radec,
+            flux_type: FluxDensityType::PowerLaw {
+                si: 0.0,
+                fd: FluxDensity {
+                    freq: FREQ,
+                    i: flux,
+                    q: 0.0,
+                    u: 0.0,
+                    v: 0.0,
+                },
+            },
+        };
+    }
+
+    /// Baselines between antennas at these (east, north) positions [m].
+    fn baselines(positions: &[(f64, f64)]) -> Array3<f64> {
+        let n = positions.len();
+        return Array3::from_shape_fn((n, n, 2), |(a, b, k)| match k {
+            0 => positions[a].0 - positions[b].0,
+            _ => positions[a].1 - positions[b].1,
+        });
+    }
+
+    fn fisher(baselines_xy: &Array3<f64>, comps: &[&Component], sigma: f64) -> Array2<Complex64> {
+        let num_ants = baselines_xy.len_of(Axis(0));
+        return fisher_matrix(
+            baselines_xy,
+            comps,
+            physical_constants::SPEED_OF_LIGHT_IN_VACUUM / FREQ,
+            FREQ,
+            sigma,
+            RADec::from_degrees(0.0, -27.0),
+            Averaging {
+                channel_width: 0.0,
+                int_time: 0.0,
+            },
+            &Array2::from_elem((num_ants, num_ants), true),
+            None,
+        );
+    }
+
+    #[test]
+    fn single_source_at_phase_centre() {
+        // |M_ab| = B on every baseline, so F_ab = 2 B^2 / sigma^2 and each
+        // diagonal sums the antenna's n - 1 baselines.
+        let (flux, sigma) = (3.0, 0.5);
+        let source = point(RADec::from_degrees(0.0, -27.0), flux);
+        let baselines_xy = baselines(&[(0.0, 0.0), (10.0, 0.0), (0.0, 25.0), (-40.0, 7.0)]);
+        let fisher = fisher(&baselines_xy, &[&source], sigma);
+
+        let expected = 2.0 * flux.powi(2) / sigma.powi(2);
+        for ((a, b), f) in fisher.indexed_iter() {
+            let expected = if a == b { 3.0 * expected } else { expected };
+            assert!((f - expected).norm() < 1e-9 * expected, "F[{a}, {b}] = {f}");
+        }
+    }
+
+    #[test]
+    fn autocorrelations_follow_the_selection() {
+        let (flux, sigma, sigma_auto) = (3.0, 0.5, 2.0);
+        let source = point(RADec::from_degrees(0.0, -27.0), flux);
+        let baselines_xy = baselines(&[(0.0, 0.0), (10.0, 0.0), (0.0, 25.0)]);
+        let mut selection = Array2::from_elem((3, 3), true);
+        selection[[1, 1]] = false;
+        let fisher = fisher_matrix(
+            &baselines_xy,
+            &[&source],
+            physical_constants::SPEED_OF_LIGHT_IN_VACUUM / FREQ,
+            FREQ,
+            sigma,
+            RADec::from_degrees(0.0, -27.0),
+            Averaging {
+                channel_width: 0.0,
+                int_time: 0.0,
+            },
+            &selection,
+            Some(sigma_auto),
+        );
+
+        // Both cross baselines, plus the autocorrelation where selected
+        let cross = 2.0 * 2.0 * flux.powi(2) / sigma.powi(2);
+        let auto = (2.0 * flux / sigma_auto).powi(2);
+        let expected = [cross + auto, cross, cross + auto];
+        for (f, e) in fisher.diag().iter().zip(expected) {
+            assert!((f.re - e).abs() < 1e-9 * e, "{f} != {e}");
+        }
+        assert_eq!(num_selected_baselines(&selection), 3);
+    }
+
+    #[test]
+    fn phase_crb_needs_a_gauge() {
+        // The phase Fisher matrix is c (n I - J) with c = 2 B^2 / sigma^2, so
+        // on the complement of the overall phase the bound is
+        // (I - J / n) / (c n).
+        let (flux, sigma) = (2.0, 0.5);
+        let source = point(RADec::from_degrees(0.0, -27.0), flux);
+        let baselines_xy = baselines(&[(0.0, 0.0), (10.0, 0.0), (0.0, 25.0), (-40.0, 7.0)]);
+        let phase = phase_fisher(&fisher(&baselines_xy, &[&source], sigma));
+        let overall_phase = Array2::<Complex64>::ones((4, 1));
+
+        assert!(calculate_crb(&phase, &Gauge::Inverse, &overall_phase).is_err());
+
+        let c = 2.0 * flux.powi(2) / sigma.powi(2);
+        for gauge in [Gauge::Project, Gauge::Pseudoinverse] {
+            let crb = calculate_crb(&phase, &gauge, &overall_phase).unwrap();
+            assert_eq!(crb.null_space.ncols(), 1);
+            for ((a, b), x) in crb.matrix.indexed_iter() {
+                let identity = if a == b { 1.0 } else { 0.0 };
+                let expected = (identity - 0.25) / (4.0 * c);
+                assert!(
+                    (x.re - expected).abs() < 1e-9,
+                    "{gauge:?}: {x} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_double_sum() {
+        let phase_centre = RADec::from_degrees(0.0, -27.0);
+        let sources = [
+            point(RADec::from_degrees(2.0, -25.0), 5.0),
+            point(RADec::from_degrees(-3.0, -29.0), 2.0),
+            point(RADec::from_degrees(1.0, -30.0), 0.7),
+        ];
+        let comps: Vec<&Component> = sources.iter().collect();
+        let baselines_xy = baselines(&[(0.0, 0.0), (12.0, 3.0), (-5.0, 30.0)]);
+        let fisher = fisher(&baselines_xy, &comps, 1.0);
+
+        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / FREQ;
+        let lm: Vec<(f64, f64)> = sources
+            .iter()
+            .map(|s| {
+                let lmn = s.radec.to_lmn(phase_centre);
+                (lmn.l, lmn.m)
+            })
+            .collect();
+        for a in 0..3 {
+            for b in (a + 1)..3 {
+                let (u, v) = (
+                    baselines_xy[[a, b, 0]] / lambda,
+                    baselines_xy[[a, b, 1]] / lambda,
+                );
+                let mut double_sum = 0.0;
+                for (i, si) in sources.iter().enumerate() {
+                    for (j, sj) in sources.iter().enumerate() {
+                        let phase = TAU * (u * (lm[i].0 - lm[j].0) + v * (lm[i].1 - lm[j].1));
+                        double_sum +=
+                            si.estimate_at_freq(FREQ).i * sj.estimate_at_freq(FREQ).i * phase.cos();
+                    }
+                }
+                let f = fisher[[a, b]];
+                assert!(
+                    (f.re - 2.0 * double_sum).abs() < 1e-9 * double_sum,
+                    "F[{a}, {b}] = {f}"
+                );
+                assert_eq!(f.im, 0.0);
+            }
         }
     }
 
-    crb = Complex::new(2.0 / sigma.powi(2), 0.0) * crb;
+    #[test]
+    fn decorrelation_is_a_sinc() {
+        let averaging = Averaging {
+            channel_width: 1e6,
+            int_time: 8.0,
+        };
+        assert_eq!(
+            averaging.decorrelation(300.0, -200.0, 0.0, 0.0, FREQ, -0.5),
+            1.0
+        );
+
+        // East-west baseline: bandwidth smearing follows the source's offset
+        // along the baseline and time smearing its offset across it.
+        let (u, l, m, dec) = (500.0, 0.1, 0.05, -0.5f64);
+        let x = PI * u * l * averaging.channel_width / FREQ;
+        let y = PI * EARTH_ROTATION_RATE * averaging.int_time * dec.sin() * u * m;
+        let expected = (x.sin() / x) * (y.sin() / y);
+        let d = averaging.decorrelation(u, 0.0, l, m, FREQ, dec);
+        assert!((d - expected).abs() < 1e-12, "{d} != {expected}");
+        assert!(d < 1.0);
+    }
+
+    #[test]
+    fn decorrelation_reduces_the_fisher_information() {
+        let (flux, sigma) = (3.0, 0.5);
+        let phase_centre = RADec::from_degrees(0.0, -27.0);
+        let source = point(RADec::from_degrees(2.0, -27.0), flux);
+        let baselines_xy = baselines(&[(0.0, 0.0), (300.0, 0.0)]);
+        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / FREQ;
+        let averaging = Averaging {
+            channel_width: 10e6,
+            int_time: 0.0,
+        };
+        let fisher = fisher_matrix(
+            &baselines_xy,
+            &[&source],
+            lambda,
+            FREQ,
+            sigma,
+            phase_centre,
+            averaging,
+            &Array2::from_elem((2, 2), true),
+            None,
+        );
+
+        // F_01 = 2 (B D)^2 / sigma^2 with D the decorrelation on the baseline.
+        let lmn = source.radec.to_lmn(phase_centre);
+        let (u, v) = (
+            baselines_xy[[0, 1, 0]] / lambda,
+            baselines_xy[[0, 1, 1]] / lambda,
+        );
+        let d = averaging.decorrelation(u, v, lmn.l, lmn.m, FREQ, phase_centre.dec);
+        assert!(d < 0.99);
+        let expected = 2.0 * (flux * d).powi(2) / sigma.powi(2);
+        let f = fisher[[0, 1]];
+        assert!((f.re - expected).abs() < 1e-9 * expected, "F[0, 1] = {f}");
+    }
 }
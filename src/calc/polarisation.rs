@@ -0,0 +1,130 @@
+//! Full-polarisation Fisher matrix. Each antenna has a 2x2 Jones matrix (8
+//! real parameters) and the model coherency of every baseline is built from
+//! all four Stokes parameters, `V_ab = J_a C_ab J_b^H`.
+
+use marlu::RADec;
+use ndarray::prelude::*;
+use num_complex::*;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+use super::{add_visibility_information, linalg, Averaging, CalcError, PINV_RCOND};
+use crate::srclist::*;
+
+/// Names of each antenna's real Jones parameters, in Fisher-matrix order.
+pub const JONES_PARAMETERS: [&str; 8] = [
+    "Re Jxx", "Im Jxx", "Re Jxy", "Im Jxy", "Re Jyx", "Im Jyx", "Re Jyy", "Im Jyy",
+];
+
+/// Build the Fisher information matrix of every antenna's Jones matrix,
+/// evaluated at `J = 1`. Parameter `8 * a + k` is `JONES_PARAMETERS[k]` of
+/// antenna `a`. Each of the four correlations of a baseline is treated as
+/// an independent measurement with noise `sigma`. Only baselines in
+/// `selection` are used.
+#[allow(clippy::too_many_arguments)]
+pub fn full_pol_fisher(
+    baselines_xy: &Array3<f64>,
+    source_list: &[&Component],
+    lambda: f64,
+    freq: f64,
+    sigma: f64,
+    phase_centre: RADec,
+    averaging: Averaging,
+    selection: &Array2<bool>,
+) -> Array2<Complex64> {
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let coherencies = model_coherencies(
+        baselines_xy,
+        source_list,
+        lambda,
+        freq,
+        phase_centre,
+        averaging,
+    );
+
+    let mut fisher = Array2::<Complex64>::zeros((8 * num_ants, 8 * num_ants));
+    let i = Complex64::new(0.0, 1.0);
+    for a in 0..num_ants {
+        for b in (a + 1)..num_ants {
+            if !selection[[a, b]] {
+                continue;
+            }
+            let c = |r: usize, s: usize| coherencies[[a, b, 2 * r + s]];
+            for r in 0..2 {
+                for s in 0..2 {
+                    // d(J_a C J_b^H)_rs / dJ_a[r, q] = C[q, s] and
+                    // d(J_a C J_b^H)_rs / dJ_b[s, q]^* = C[r, q].
+                    let mut derivatives = Vec::with_capacity(8);
+                    for q in 0..2 {
+                        let p_a = 8 * a + 2 * (2 * r + q);
+                        derivatives.push((p_a, c(q, s)));
+                        derivatives.push((p_a + 1, i * c(q, s)));
+
+                        let p_b = 8 * b + 2 * (2 * s + q);
+                        derivatives.push((p_b, c(r, q)));
+                        derivatives.push((p_b + 1, -i * c(r, q)));
+                    }
+                    add_visibility_information(&mut fisher, &derivatives, sigma);
+                }
+            }
+        }
+    }
+
+    return fisher;
+}
+
+/// The CRB of every antenna's Jones parameters (columns ordered as
+/// `JONES_PARAMETERS`). The full-polarisation Fisher matrix is always
+/// singular (at least an overall phase, and a unitary rotation when the sky
+/// is unpolarised), so a pseudo-inverse is used; the number of discarded
+/// degenerate directions is also returned.
+pub fn jones_crb(fisher: &Array2<Complex64>) -> Result<(Array2<f64>, usize), CalcError> {
+    let (crb, null_space) = linalg::pinv_hermitian(fisher, PINV_RCOND)?;
+    let num_ants = fisher.nrows() / 8;
+    let jones_crb = Array2::from_shape_fn((num_ants, 8), |(a, k)| crb[[8 * a + k, 8 * a + k]].re);
+    return Ok((jones_crb, null_space.ncols()));
+}
+
+/// The model coherency `[XX, XY, YX, YY]` of every baseline `a < b`, with
+/// each component contributing `[I + Q, U + iV, U - iV, I - Q]`.
+fn model_coherencies(
+    baselines_xy: &Array3<f64>,
+    source_list: &[&Component],
+    lambda: f64,
+    freq: f64,
+    phase_centre: RADec,
+    averaging: Averaging,
+) -> Array3<Complex64> {
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let baselines = baselines_xy / lambda;
+
+    let comps: Vec<(FluxDensity, f64, f64)> = source_list
+        .iter()
+        .map(|comp| {
+            let lmn = comp.radec.to_lmn(phase_centre);
+            (comp.estimate_at_freq(freq), lmn.l, lmn.m)
+        })
+        .collect();
+
+    let mut coherencies = Array3::<Complex64>::zeros((num_ants, num_ants, 4));
+    coherencies
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .for_each(|(a, mut rows)| {
+            for b in (a + 1)..num_ants {
+                let (u, v) = (baselines[[a, b, 0]], baselines[[a, b, 1]]);
+                for (fd, l, m) in comps.iter() {
+                    let decorrelation =
+                        averaging.decorrelation(u, v, *l, *m, freq, phase_centre.dec);
+                    let fringe = Complex64::from_polar(decorrelation, 2.0 * PI * (u * l + v * m));
+                    rows[[b, 0]] += fringe * (fd.i + fd.q);
+                    rows[[b, 1]] += fringe * Complex64::new(fd.u, fd.v);
+                    rows[[b, 2]] += fringe * Complex64::new(fd.u, -fd.v);
+                    rows[[b, 3]] += fringe * (fd.i - fd.q);
+                }
+            }
+        });
+
+    return coherencies;
+}
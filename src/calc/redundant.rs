@@ -0,0 +1,289 @@
+//! Redundant calibration. Baselines with the same separation measure the
+//! same sky visibility, so instead of a sky model each redundant group has a
+//! free visibility `y_g` and `V_ab = g_a g_b^* y_g`. Only the relative gains
+//! can be found this way: the overall amplitude, overall phase and a phase
+//! gradient across the array (tip and tilt) are degenerate.
+
+use log::warn;
+use ndarray::prelude::*;
+use num_complex::*;
+
+use super::{add_visibility_information, linalg, CalcError};
+
+/// The gauge directions are treated as exact degeneracies only if the Fisher
+/// matrix maps them to less than this fraction of its largest element.
+const GAUGE_TOLERANCE: f64 = 1e-6;
+
+/// A set of baselines that share (within the tolerance) the same separation.
+pub struct RedundantGroup {
+    /// Antenna pairs `(a, b)`, ordered so their separations all point the
+    /// same way
+    pub baselines: Vec<(usize, usize)>,
+
+    /// Mean separation (east, north) [m]
+    pub separation: [f64; 2],
+}
+
+pub struct RedundantCrb {
+    /// The antennas in at least one redundant group, in parameter order
+    pub antennas: Vec<usize>,
+
+    /// CRB of each antenna's log-amplitude gain
+    pub amplitude: Array1<f64>,
+
+    /// CRB of each antenna's gain phase [rad^2]
+    pub phase: Array1<f64>,
+
+    /// CRB of each group's visibility (the sum of the real and imaginary
+    /// variances) [Jy^2]
+    pub visibility: Array1<f64>,
+}
+
+/// Group every baseline `a < b` whose separation matches another's (or its
+/// reverse) to within `tolerance` metres. Baselines that are not redundant
+/// with any other are dropped, as they carry no information on the gains.
+pub fn redundant_groups(baselines_xy: &Array3<f64>, tolerance: f64) -> Vec<RedundantGroup> {
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let mut groups: Vec<RedundantGroup> = vec![];
+
+    for a in 0..num_ants {
+        for b in (a + 1)..num_ants {
+            let (x, y) = (baselines_xy[[a, b, 0]], baselines_xy[[a, b, 1]]);
+            let mut found = false;
+            for group in groups.iter_mut() {
+                let [gx, gy] = group.separation;
+                if (x - gx).hypot(y - gy) < tolerance {
+                    group.baselines.push((a, b));
+                    found = true;
+                } else if (x + gx).hypot(y + gy) < tolerance {
+                    group.baselines.push((b, a));
+                    found = true;
+                }
+                if found {
+                    break;
+                }
+            }
+            if !found {
+                groups.push(RedundantGroup {
+                    baselines: vec![(a, b)],
+                    separation: [x, y],
+                });
+            }
+        }
+    }
+
+    groups.retain(|group| group.baselines.len() > 1);
+    for group in groups.iter_mut() {
+        let num = group.baselines.len() as f64;
+        group.separation = group.baselines.iter().fold([0.0, 0.0], |sum, &(a, b)| {
+            [
+                sum[0] + baselines_xy[[a, b, 0]] / num,
+                sum[1] + baselines_xy[[a, b, 1]] / num,
+            ]
+        });
+    }
+
+    return groups;
+}
+
+/// The redundant-calibration CRB, evaluated at `g = 1` and with each group's
+/// visibility set to the mean of `model` over its baselines. Antenna `i` of
+/// the result has parameters `2 i` (log-amplitude) and `2 i + 1` (phase), and
+/// group `g` has `2 num_ants + 2 g` (real) and `2 num_ants + 2 g + 1`
+/// (imaginary).
+///
+/// Only the baselines in `selection` are used, and groups left with fewer
+/// than two of them are dropped; `None` if there are no groups left.
+///
+/// The gauge is fixed by requiring the errors to be orthogonal to the four
+/// degenerate directions: with `Q` an orthonormal basis for them and `s` the
+/// largest diagonal element of `F`, the CRB is `(F + s Q Q^T)^-1 - Q Q^T / s`
+/// (see `linalg::inv_projected`).
+pub fn redundant_crb(
+    baselines_xy: &Array3<f64>,
+    groups: &[RedundantGroup],
+    model: &Array2<Complex64>,
+    sigma: f64,
+    selection: &Array2<bool>,
+) -> Result<Option<RedundantCrb>, CalcError> {
+    let groups: Vec<RedundantGroup> = groups
+        .iter()
+        .map(|group| RedundantGroup {
+            baselines: group
+                .baselines
+                .iter()
+                .copied()
+                .filter(|&(a, b)| selection[[a, b]])
+                .collect(),
+            separation: group.separation,
+        })
+        .filter(|group| group.baselines.len() > 1)
+        .collect();
+    if groups.is_empty() {
+        return Ok(None);
+    }
+
+    let mut antennas: Vec<usize> = groups
+        .iter()
+        .flat_map(|group| group.baselines.iter().flat_map(|&(a, b)| [a, b]))
+        .collect();
+    antennas.sort_unstable();
+    antennas.dedup();
+    let index = |ant: usize| antennas.binary_search(&ant).unwrap();
+
+    let num_ants = antennas.len();
+    let num_params = 2 * num_ants + 2 * groups.len();
+    let i = Complex64::new(0.0, 1.0);
+
+    let visibilities: Vec<Complex64> = groups
+        .iter()
+        .map(|group| {
+            group
+                .baselines
+                .iter()
+                .map(|&(a, b)| model[[a, b]])
+                .sum::<Complex64>()
+                / group.baselines.len() as f64
+        })
+        .collect();
+
+    let mut fisher = Array2::<Complex64>::zeros((num_params, num_params));
+    for (g, (group, &y)) in groups.iter().zip(visibilities.iter()).enumerate() {
+        let p_y = 2 * num_ants + 2 * g;
+        for &(a, b) in group.baselines.iter() {
+            let (p_a, p_b) = (2 * index(a), 2 * index(b));
+            let derivatives = [
+                (p_a, y),
+                (p_a + 1, i * y),
+                (p_b, y),
+                (p_b + 1, -i * y),
+                (p_y, Complex64::new(1.0, 0.0)),
+                (p_y + 1, i),
+            ];
+            add_visibility_information(&mut fisher, &derivatives, sigma);
+        }
+    }
+
+    // Changes of the parameters that leave every visibility unchanged:
+    // scaling all gains (with y_g scaled inversely), rotating all phases, and
+    // a phase gradient across the array (with y_g counter-rotated). Antenna
+    // positions relative to antenna 0 are enough, as any offset is absorbed
+    // by the overall phase.
+    let mut gauge = Array2::<f64>::zeros((num_params, 4));
+    for (k, &ant) in antennas.iter().enumerate() {
+        gauge[[2 * k, 0]] = 1.0;
+        gauge[[2 * k + 1, 1]] = 1.0;
+        gauge[[2 * k + 1, 2]] = baselines_xy[[ant, 0, 0]];
+        gauge[[2 * k + 1, 3]] = baselines_xy[[ant, 0, 1]];
+    }
+    for (g, (group, &y)) in groups.iter().zip(visibilities.iter()).enumerate() {
+        let p_y = 2 * num_ants + 2 * g;
+        let dy = [
+            -2.0 * y,
+            -i * group.separation[0] * y,
+            -i * group.separation[1] * y,
+        ];
+        for (col, dy) in [0, 2, 3].into_iter().zip(dy) {
+            gauge[[p_y, col]] = dy.re;
+            gauge[[p_y + 1, col]] = dy.im;
+        }
+    }
+    let gauge = linalg::orthonormalise(gauge.mapv(|q| Complex64::new(q, 0.0)));
+    let leakage = fisher
+        .dot(&gauge)
+        .iter()
+        .fold(0.0, |max: f64, f| max.max(f.norm()));
+    let scale = fisher.iter().fold(0.0, |max: f64, f| max.max(f.norm()));
+    if leakage > GAUGE_TOLERANCE * scale {
+        warn!(
+            "Gauge directions are not exactly degenerate (leakage {:e} of {:e})",
+            leakage, scale
+        );
+    }
+
+    let crb = linalg::inv_projected(&fisher, &gauge)?;
+    let diag = crb.diag().mapv(|c| c.re);
+
+    return Ok(Some(RedundantCrb {
+        antennas,
+        amplitude: diag.slice(s![..2 * num_ants;2]).to_owned(),
+        phase: diag.slice(s![1..2 * num_ants;2]).to_owned(),
+        visibility: Array1::from_shape_fn(groups.len(), |g| {
+            diag[2 * num_ants + 2 * g] + diag[2 * num_ants + 2 * g + 1]
+        }),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Baselines between antennas at these (east, north) positions [m].
+    fn baselines(positions: &[(f64, f64)]) -> Array3<f64> {
+        let n = positions.len();
+        return Array3::from_shape_fn((n, n, 2), |(a, b, k)| match k {
+            0 => positions[a].0 - positions[b].0,
+            _ => positions[a].1 - positions[b].1,
+        });
+    }
+
+    /// A 3x3 grid with 10 m spacing; antenna 4 is in the middle.
+    fn grid() -> Array3<f64> {
+        let positions: Vec<(f64, f64)> = (0..9)
+            .map(|i| (10.0 * (i % 3) as f64, 10.0 * (i / 3) as f64))
+            .collect();
+        return baselines(&positions);
+    }
+
+    #[test]
+    fn groups_on_a_line() {
+        let baselines_xy = baselines(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0), (30.0, 0.0)]);
+        let groups = redundant_groups(&baselines_xy, 0.1);
+        // 10 m and 20 m are redundant; the single 30 m baseline is dropped.
+        let mut sizes: Vec<usize> = groups.iter().map(|g| g.baselines.len()).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, vec![2, 3]);
+        for group in groups.iter() {
+            for &(a, b) in group.baselines.iter() {
+                assert!((baselines_xy[[a, b, 0]] - group.separation[0]).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_crb() {
+        let baselines_xy = grid();
+        let groups = redundant_groups(&baselines_xy, 0.1);
+        let model = Array2::from_elem((9, 9), Complex64::new(5.0, 0.0));
+        let selection = Array2::from_elem((9, 9), true);
+        let crb = redundant_crb(&baselines_xy, &groups, &model, 1.0, &selection)
+            .unwrap()
+            .unwrap();
+        assert_eq!(crb.antennas, (0..9).collect::<Vec<_>>());
+        assert!(crb
+            .amplitude
+            .iter()
+            .chain(crb.phase.iter())
+            .all(|&c| c > 0.0));
+        // The middle antenna is in the most redundant baselines.
+        assert!(crb.amplitude[4] < crb.amplitude[0]);
+
+        // The CRB scales with the noise variance.
+        let noisier = redundant_crb(&baselines_xy, &groups, &model, 2.0, &selection)
+            .unwrap()
+            .unwrap();
+        for (c, n) in crb.amplitude.iter().zip(noisier.amplitude.iter()) {
+            assert!((n - 4.0 * c).abs() < 1e-9 * n, "{n} != 4 * {c}");
+        }
+    }
+
+    #[test]
+    fn nothing_selected() {
+        let baselines_xy = grid();
+        let groups = redundant_groups(&baselines_xy, 0.1);
+        let model = Array2::from_elem((9, 9), Complex64::new(5.0, 0.0));
+        let selection = Array2::from_elem((9, 9), false);
+        let crb = redundant_crb(&baselines_xy, &groups, &model, 1.0, &selection).unwrap();
+        assert!(crb.is_none());
+    }
+}
@@ -0,0 +1,212 @@
+//! Imperfect sky models. The Stokes I flux densities of the brightest
+//! components are treated as unknowns estimated alongside the gains, and the
+//! gain CRB is found after marginalising over them.
+
+use marlu::RADec;
+use ndarray::prelude::*;
+use num_complex::*;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+use super::{fisher_from_jacobian, linalg, Averaging, CalcError};
+use crate::srclist::*;
+
+pub struct SkyCrb {
+    /// CRB of each antenna's log-amplitude gain with the fluxes known
+    pub gains_known: Array1<f64>,
+
+    /// CRB of each antenna's log-amplitude gain after marginalising over the
+    /// free fluxes
+    pub gains_marginal: Array1<f64>,
+
+    /// CRB of each free flux density [Jy^2]
+    pub fluxes: Array1<f64>,
+}
+
+/// The indices of the `num_free` components with the largest Stokes I flux
+/// density at `freq`, brightest first.
+pub fn brightest(source_list: &[&Component], num_free: usize, freq: f64) -> Vec<usize> {
+    let fluxes: Vec<f64> = source_list
+        .iter()
+        .map(|comp| comp.estimate_at_freq(freq).i)
+        .collect();
+    let mut indices: Vec<usize> = (0..fluxes.len()).collect();
+    indices.sort_by(|&a, &b| fluxes[b].total_cmp(&fluxes[a]));
+    indices.truncate(num_free);
+    return indices;
+}
+
+/// Build the joint Fisher information matrix of every antenna's
+/// log-amplitude gain (parameters `0..num_ants`) and the flux density of
+/// each component in `free` (parameter `num_ants + k` for `free[k]`),
+/// evaluated at `g = 1`, using only the baselines in `selection`. The
+/// derivative with respect to a flux is that component's unit-flux fringe.
+///
+/// A common gain amplitude is degenerate with an overall flux scale, so if
+/// every component is free the matrix is singular.
+#[allow(clippy::too_many_arguments)]
+pub fn sky_gain_fisher(
+    baselines_xy: &Array3<f64>,
+    source_list: &[&Component],
+    free: &[usize],
+    lambda: f64,
+    freq: f64,
+    sigma: f64,
+    phase_centre: RADec,
+    averaging: Averaging,
+    selection: &Array2<bool>,
+) -> Array2<Complex64> {
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let baselines = baselines_xy / lambda;
+
+    // The parameter index of each component's flux, if it is free
+    let comps: Vec<(Option<usize>, f64, f64, f64)> = source_list
+        .iter()
+        .enumerate()
+        .map(|(c, comp)| {
+            let lmn = comp.radec.to_lmn(phase_centre);
+            let param = free.iter().position(|&f| f == c).map(|k| num_ants + k);
+            (param, comp.estimate_at_freq(freq).i, lmn.l, lmn.m)
+        })
+        .collect();
+
+    let pairs: Vec<(usize, usize)> = (0..num_ants)
+        .flat_map(|a| ((a + 1)..num_ants).map(move |b| (a, b)))
+        .filter(|&(a, b)| selection[[a, b]])
+        .collect();
+
+    let mut jacobian = Array2::<Complex64>::zeros((pairs.len(), num_ants + free.len()));
+    jacobian
+        .axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .zip(pairs.par_iter())
+        .for_each(|(mut row, &(a, b))| {
+            let (u, v) = (baselines[[a, b, 0]], baselines[[a, b, 1]]);
+            let mut model = Complex64::new(0.0, 0.0);
+            for &(param, flux, l, m) in comps.iter() {
+                let decorrelation = averaging.decorrelation(u, v, l, m, freq, phase_centre.dec);
+                let fringe = Complex64::from_polar(decorrelation, 2.0 * PI * (u * l + v * m));
+                model += flux * fringe;
+                if let Some(p) = param {
+                    row[p] = fringe;
+                }
+            }
+            row[a] = model;
+            row[b] = model;
+        });
+
+    return fisher_from_jacobian(&jacobian, sigma);
+}
+
+/// The gain CRB with the fluxes known (the inverse of the gain block) and
+/// marginalised over the fluxes (the inverse of the Schur complement
+/// `F_gg - F_gs F_ss^-1 F_sg`), along with the CRB of the fluxes themselves.
+pub fn marginal_gain_crb(fisher: &Array2<Complex64>, num_ants: usize) -> Result<SkyCrb, CalcError> {
+    let f_gg = fisher.slice(s![..num_ants, ..num_ants]);
+    let f_gs = fisher.slice(s![..num_ants, num_ants..]);
+    let f_ss = fisher.slice(s![num_ants.., num_ants..]);
+
+    let gains_known = linalg::inv_hermitian(&f_gg.to_owned())?;
+    let f_ss_inv = linalg::inv_hermitian(&f_ss.to_owned())?;
+    let schur = &f_gg - &f_gs.dot(&f_ss_inv).dot(&f_gs.t());
+    let gains_marginal = linalg::inv_hermitian(&schur)?;
+
+    // The flux block of the full inverse is the inverse of the other Schur
+    // complement, F_ss - F_sg F_gg^-1 F_gs.
+    let flux_schur = &f_ss - &f_gs.t().dot(&gains_known).dot(&f_gs);
+    let fluxes = linalg::inv_hermitian(&flux_schur)?;
+
+    return Ok(SkyCrb {
+        gains_known: gains_known.diag().mapv(|c| c.re),
+        gains_marginal: gains_marginal.diag().mapv(|c| c.re),
+        fluxes: fluxes.diag().mapv(|c| c.re),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FREQ: f64 = 150e6;
+
+    fn point(radec: RADec, flux: f64) -> Component {
+        return Component {
+            radec,
+            flux_type: FluxDensityType::PowerLaw {
+                si: 0.0,
+                fd: FluxDensity {
+                    freq: FREQ,
+                    i: flux,
+                    q: 0.0,
+                    u: 0.0,
+                    v: 0.0,
+                },
+            },
+        };
+    }
+
+    #[test]
+    fn brightest_first() {
+        let comps: Vec<Component> = [2.0, 7.0, 0.5, 3.0]
+            .iter()
+            .map(|&flux| point(RADec::from_degrees(0.0, -27.0), flux))
+            .collect();
+        let comps: Vec<&Component> = comps.iter().collect();
+        assert_eq!(brightest(&comps, 2, FREQ), vec![1, 3]);
+        assert_eq!(brightest(&comps, 10, FREQ), vec![1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn marginalising_matches_the_full_inverse() {
+        let positions = [
+            (0.0, 0.0),
+            (35.0, 4.0),
+            (-12.0, 50.0),
+            (80.0, -30.0),
+            (-60.0, -45.0),
+        ];
+        let num_ants = positions.len();
+        let baselines_xy = Array3::from_shape_fn((num_ants, num_ants, 2), |(a, b, k)| match k {
+            0 => positions[a].0 - positions[b].0,
+            _ => positions[a].1 - positions[b].1,
+        });
+        let comps = [
+            point(RADec::from_degrees(0.5, -27.0), 5.0),
+            point(RADec::from_degrees(-2.0, -25.0), 3.0),
+            point(RADec::from_degrees(3.0, -30.0), 1.0),
+        ];
+        let comps: Vec<&Component> = comps.iter().collect();
+        let free = brightest(&comps, 2, FREQ);
+        let fisher = sky_gain_fisher(
+            &baselines_xy,
+            &comps,
+            &free,
+            physical_constants::SPEED_OF_LIGHT_IN_VACUUM / FREQ,
+            FREQ,
+            0.5,
+            RADec::from_degrees(0.0, -27.0),
+            Averaging {
+                channel_width: 0.0,
+                int_time: 0.0,
+            },
+            &Array2::from_elem((num_ants, num_ants), true),
+        );
+        assert_eq!(fisher.dim(), (num_ants + 2, num_ants + 2));
+
+        let crb = marginal_gain_crb(&fisher, num_ants).unwrap();
+        let full = linalg::inv_hermitian(&fisher).unwrap();
+        for a in 0..num_ants {
+            let expected = full[[a, a]].re;
+            let marginal = crb.gains_marginal[a];
+            assert!(
+                (marginal - expected).abs() < 1e-9 * expected,
+                "{marginal} != {expected}"
+            );
+            assert!(marginal > crb.gains_known[a]);
+        }
+        for k in 0..free.len() {
+            let expected = full[[num_ants + k, num_ants + k]].re;
+            assert!((crb.fluxes[k] - expected).abs() < 1e-9 * expected);
+        }
+    }
+}
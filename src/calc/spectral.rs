@@ -0,0 +1,188 @@
+//! A joint Fisher matrix over all channels, where each antenna's gain is a
+//! smooth function of frequency (a sum of spectral basis functions) rather
+//! than being free in every channel.
+
+use ndarray::prelude::*;
+use num_complex::*;
+use std::f64::consts::TAU;
+
+use super::{linalg, CalcError};
+use crate::config::SpectralBasis;
+
+pub struct SpectralCrb {
+    /// CRB of the basis coefficients, indexed by `antenna * num_basis + k`
+    pub coefficients: Array2<Complex64>,
+
+    /// CRB of each antenna's reconstructed gain (columns) in each channel
+    /// (rows)
+    pub bandpass: Array2<f64>,
+}
+
+impl SpectralBasis {
+    /// Evaluate every basis function (columns) at every frequency (rows).
+    /// Frequencies are first mapped onto the unit interval across the band.
+    pub fn evaluate(&self, freqs: &[f64]) -> Array2<f64> {
+        let min = freqs.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = freqs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let span = if max > min { max - min } else { 1.0 };
+
+        let mut basis = Array2::<f64>::zeros((freqs.len(), self.num_functions()));
+        for (mut row, freq) in basis.axis_iter_mut(Axis(0)).zip(freqs) {
+            let t = (freq - min) / span;
+            match self {
+                // Legendre polynomials on [-1, 1], which are far better
+                // conditioned than plain powers of frequency.
+                SpectralBasis::Polynomial(order) => {
+                    let x = 2.0 * t - 1.0;
+                    row[0] = 1.0;
+                    if *order >= 1 {
+                        row[1] = x;
+                    }
+                    for k in 1..*order {
+                        let k_f = k as f64;
+                        row[k + 1] =
+                            ((2.0 * k_f + 1.0) * x * row[k] - k_f * row[k - 1]) / (k_f + 1.0);
+                    }
+                }
+
+                SpectralBasis::Fourier(order) => {
+                    row[0] = 1.0;
+                    for k in 1..=*order {
+                        let (sin, cos) = (TAU * k as f64 * t).sin_cos();
+                        row[2 * k - 1] = cos;
+                        row[2 * k] = sin;
+                    }
+                }
+            }
+        }
+
+        return basis;
+    }
+
+    /// The number of basis functions.
+    pub fn num_functions(&self) -> usize {
+        match self {
+            SpectralBasis::Polynomial(order) => order + 1,
+            SpectralBasis::Fourier(order) => 2 * order + 1,
+        }
+    }
+}
+
+/// Combine the Fisher matrices of every channel into a single Fisher matrix
+/// for the basis coefficients, `F[(a, k), (b, k')] = sum_f phi_k(f)
+/// phi_k'(f) F_f[a, b]`, and invert it. The bandpass CRB of antenna `a` at
+/// frequency `f` is then `phi(f)^T C_a phi(f)`, where `C_a` is that
+/// antenna's block of the coefficient CRB.
+pub fn calculate_spectral_crb(
+    fishers: &[Array2<Complex64>],
+    freqs: &[f64],
+    basis: &SpectralBasis,
+) -> Result<SpectralCrb, CalcError> {
+    let phi = basis.evaluate(freqs);
+    let num_basis = basis.num_functions();
+    let num_ants = fishers[0].nrows();
+    let num_params = num_ants * num_basis;
+
+    let mut joint = Array2::<Complex64>::zeros((num_params, num_params));
+    for (fisher, phi_f) in fishers.iter().zip(phi.axis_iter(Axis(0))) {
+        for a in 0..num_ants {
+            for b in 0..num_ants {
+                let f_ab = fisher[[a, b]];
+                for k in 0..num_basis {
+                    for k2 in 0..num_basis {
+                        joint[[a * num_basis + k, b * num_basis + k2]] +=
+                            phi_f[k] * phi_f[k2] * f_ab;
+                    }
+                }
+            }
+        }
+    }
+
+    let coefficients = linalg::inv_hermitian(&joint)?;
+
+    let mut bandpass = Array2::<f64>::zeros((freqs.len(), num_ants));
+    for (mut row, phi_f) in bandpass.axis_iter_mut(Axis(0)).zip(phi.axis_iter(Axis(0))) {
+        for (a, crb) in row.iter_mut().enumerate() {
+            let block = coefficients.slice(s![
+                a * num_basis..(a + 1) * num_basis,
+                a * num_basis..(a + 1) * num_basis
+            ]);
+            let mut sum = Complex64::new(0.0, 0.0);
+            for k in 0..num_basis {
+                for k2 in 0..num_basis {
+                    sum += phi_f[k] * block[[k, k2]] * phi_f[k2];
+                }
+            }
+            *crb = sum.re;
+        }
+    }
+
+    return Ok(SpectralCrb {
+        coefficients,
+        bandpass,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A well-conditioned Fisher matrix that differs between channels.
+    fn channel_fisher(num_ants: usize, scale: f64) -> Array2<Complex64> {
+        return Array2::from_shape_fn((num_ants, num_ants), |(a, b)| {
+            let f = if a == b {
+                num_ants as f64
+            } else {
+                0.5 / (1.0 + (a + b) as f64)
+            };
+            return Complex64::new(scale * f, 0.0);
+        });
+    }
+
+    fn inverse_diagonal(fisher: &Array2<Complex64>) -> Array1<f64> {
+        return linalg::inv_hermitian(fisher).unwrap().diag().mapv(|c| c.re);
+    }
+
+    #[test]
+    fn legendre_polynomials_at_the_band_edges() {
+        let freqs = [100e6, 150e6, 200e6];
+        let basis = SpectralBasis::Polynomial(3).evaluate(&freqs);
+        // P_k(-1) = (-1)^k, P_k(1) = 1, and P_2(0) = -1/2, P_3(0) = 0.
+        assert_eq!(basis.row(0).to_vec(), vec![1.0, -1.0, 1.0, -1.0]);
+        assert_eq!(basis.row(2).to_vec(), vec![1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(basis.row(1).to_vec(), vec![1.0, 0.0, -0.5, 0.0]);
+    }
+
+    #[test]
+    fn constant_gains_average_the_channels() {
+        let fisher = channel_fisher(4, 2.0);
+        let fishers = vec![fisher.clone(); 5];
+        let freqs: Vec<f64> = (0..5).map(|i| 150e6 + 1e6 * i as f64).collect();
+        let crb = calculate_spectral_crb(&fishers, &freqs, &SpectralBasis::Polynomial(0)).unwrap();
+
+        let expected = inverse_diagonal(&fisher) / 5.0;
+        for row in crb.bandpass.axis_iter(Axis(0)) {
+            for (c, e) in row.iter().zip(expected.iter()) {
+                assert!((c - e).abs() < 1e-12 * e, "{c} != {e}");
+            }
+        }
+    }
+
+    #[test]
+    fn spanning_basis_matches_independent_channels() {
+        // With as many basis functions as channels the gains are free in
+        // every channel, so the bandpass CRB is each channel's own CRB.
+        let freqs = [140e6, 150e6, 160e6];
+        let fishers: Vec<Array2<Complex64>> = [1.0, 3.0, 0.5]
+            .iter()
+            .map(|&s| channel_fisher(3, s))
+            .collect();
+        let crb = calculate_spectral_crb(&fishers, &freqs, &SpectralBasis::Polynomial(2)).unwrap();
+
+        for (fisher, row) in fishers.iter().zip(crb.bandpass.axis_iter(Axis(0))) {
+            for (c, e) in row.iter().zip(inverse_diagonal(fisher).iter()) {
+                assert!((c - e).abs() < 1e-9 * e, "{c} != {e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+/// Errors associated with working out which frequency channels to use.
+#[derive(Error, Debug)]
+pub enum ChannelError {
+    #[error("The channel width ({channel_width} Hz) does not evenly divide the coarse channel width ({coarse_width} Hz)")]
+    UnevenFineChannels {
+        channel_width: f64,
+        coarse_width: u32,
+    },
+
+    #[error("Receiver coarse channel {0} is not in the metafits")]
+    CoarseChannelNotInMetafits(usize),
+
+    #[error("The frequency range {start} Hz to {end} Hz does not contain any channels")]
+    EmptyRange { start: f64, end: f64 },
+
+    #[error("All channels were flagged")]
+    AllFlagged,
+
+    #[error("No baselines are within the uv range at {freq} Hz")]
+    NoBaselines { freq: f64 },
+}
@@ -0,0 +1,189 @@
+//! The frequency channels the CRB is evaluated at. Channels can follow the
+//! coarse/fine channel layout of the correlator (so CRB spectra line up
+//! with calibration solutions), or be given explicitly.
+
+mod error;
+
+pub use error::*;
+
+use crate::config::{ChannelSpec, Config};
+use mwalib::{CoarseChannel, MWAVersion, MetafitsContext};
+
+/// A single frequency channel.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    /// Centre frequency [Hz]
+    pub freq: f64,
+
+    /// Receiver number of the coarse channel this fine channel belongs to
+    pub coarse_chan: Option<usize>,
+
+    /// Index of this fine channel within its coarse channel
+    pub fine_chan: Option<usize>,
+}
+
+impl std::fmt::Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.coarse_chan, self.fine_chan) {
+            (Some(coarse), Some(fine)) => {
+                write!(f, "{} Hz (coarse {}, fine {})", self.freq, coarse, fine)
+            }
+            _ => write!(f, "{} Hz", self.freq),
+        }
+    }
+}
+
+/// Get the unflagged channels described by `config`, each `channel_width`
+/// wide. Without a channel specification, channels start at `start_freq` and
+/// are spaced by exactly `channel_width`, stopping before `end_freq`, or
+/// cover every coarse channel in the metafits if there is no frequency range
+/// either.
+pub fn get_channels(
+    config: &Config,
+    metafits: &MetafitsContext,
+    channel_width: f64,
+) -> Result<Vec<Channel>, ChannelError> {
+    let all_coarse_chans = || {
+        let coarse_chans: Vec<&CoarseChannel> = metafits.metafits_coarse_chans.iter().collect();
+        fine_channels(&coarse_chans, metafits, channel_width)
+    };
+
+    let channels: Vec<Channel> = match &config.channels {
+        None => match (config.start_freq, config.end_freq) {
+            (Some(start), Some(end)) => channel_range(start, end, channel_width)?,
+            _ => all_coarse_chans()?,
+        },
+
+        Some(ChannelSpec::Metafits) => all_coarse_chans()?,
+
+        Some(ChannelSpec::Coarse(rec_chans)) => {
+            let coarse_chans = rec_chans
+                .iter()
+                .map(|&rec| {
+                    metafits
+                        .metafits_coarse_chans
+                        .iter()
+                        .find(|cc| cc.rec_chan_number == rec)
+                        .ok_or(ChannelError::CoarseChannelNotInMetafits(rec))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            fine_channels(&coarse_chans, metafits, channel_width)?
+        }
+
+        Some(ChannelSpec::Freqs(freqs)) => freqs
+            .iter()
+            .map(|&freq| Channel {
+                freq,
+                coarse_chan: None,
+                fine_chan: None,
+            })
+            .collect(),
+
+        Some(ChannelSpec::Ranges(ranges)) => {
+            let mut channels = vec![];
+            for &[start, end] in ranges.iter() {
+                channels.extend(channel_range(start, end, channel_width)?);
+            }
+            channels
+        }
+    };
+
+    let channels: Vec<Channel> = channels
+        .into_iter()
+        .enumerate()
+        .filter(|(i, chan)| {
+            let fine_flagged = chan
+                .fine_chan
+                .is_some_and(|fine| config.flagged_fine_chans.contains(&fine));
+            return !fine_flagged && !config.flagged_channels.contains(i);
+        })
+        .map(|(_, chan)| chan)
+        .collect();
+
+    if channels.is_empty() {
+        return Err(ChannelError::AllFlagged);
+    }
+
+    return Ok(channels);
+}
+
+/// Channels centred on `start + k * width` for every such frequency below
+/// `end`.
+fn channel_range(start: f64, end: f64, width: f64) -> Result<Vec<Channel>, ChannelError> {
+    // Allow a little slack so that e.g. (end - start) / width = 2.9999999 still
+    // counts as three channels.
+    let num_chans = ((end - start) / width + 1e-6).floor();
+    if num_chans.is_nan() || num_chans < 1.0 {
+        return Err(ChannelError::EmptyRange { start, end });
+    }
+
+    return Ok((0..num_chans as usize)
+        .map(|k| Channel {
+            freq: start + k as f64 * width,
+            coarse_chan: None,
+            fine_chan: None,
+        })
+        .collect());
+}
+
+/// Split coarse channels into fine channels of `channel_width`, with the same
+/// centres the correlator uses.
+fn fine_channels(
+    coarse_chans: &[&CoarseChannel],
+    metafits: &MetafitsContext,
+    channel_width: f64,
+) -> Result<Vec<Channel>, ChannelError> {
+    let coarse_width = metafits.coarse_chan_width_hz;
+    let num_fine = coarse_width as f64 / channel_width;
+    if (num_fine - num_fine.round()).abs() > 1e-6 || num_fine < 1.0 {
+        return Err(ChannelError::UnevenFineChannels {
+            channel_width,
+            coarse_width,
+        });
+    }
+    let num_fine = num_fine.round() as usize;
+
+    let mut channels = vec![];
+    for cc in coarse_chans {
+        let freqs = CoarseChannel::get_fine_chan_centres_array_hz(
+            metafits.mwa_version.unwrap_or(MWAVersion::CorrMWAXv2),
+            std::slice::from_ref(*cc),
+            channel_width.round() as u32,
+            num_fine,
+        );
+        channels.extend(freqs.into_iter().enumerate().map(|(fine, freq)| Channel {
+            freq,
+            coarse_chan: Some(cc.rec_chan_number),
+            fine_chan: Some(fine),
+        }));
+    }
+
+    return Ok(channels);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_of_channels() {
+        let freqs: Vec<f64> = channel_range(100e6, 130e6, 10e6)
+            .unwrap()
+            .iter()
+            .map(|c| c.freq)
+            .collect();
+        assert_eq!(freqs, vec![100e6, 110e6, 120e6]);
+
+        // (0.3 - 0.0) / 0.1 is just below 3 in floating point.
+        assert_eq!(channel_range(0.0, 0.3, 0.1).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn empty_range() {
+        assert!(matches!(
+            channel_range(130e6, 100e6, 10e6),
+            Err(ChannelError::EmptyRange { .. })
+        ));
+        assert!(channel_range(100e6, 105e6, 10e6).is_err());
+    }
+}
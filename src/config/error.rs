@@ -0,0 +1,74 @@
+use thiserror::Error;
+
+/// Errors associated with reading in a config file.
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Config override '{0}' is not of the form key=value")]
+    MalformedOverride(String),
+
+    #[error("Cannot override '{key}': '{parent}' is not a mapping")]
+    OverrideNotMapping { key: String, parent: String },
+
+    #[error("'{key}' should be a {expected}, not '{value}'")]
+    InvalidQuantity {
+        key: String,
+        value: String,
+        expected: String,
+    },
+
+    #[error("'{field}' must be positive, not {value}")]
+    NotPositive { field: &'static str, value: f64 },
+
+    #[error("'end_freq' ({end} Hz) must be above 'start_freq' ({start} Hz)")]
+    InvalidFrequencyRange { start: f64, end: f64 },
+
+    #[error("RA must be in [0, 360) degrees, not {0}")]
+    InvalidRa(f64),
+
+    #[error("Dec must be in [-90, 90] degrees, not {0}")]
+    InvalidDec(f64),
+
+    #[error("'{0}' and '{1}' must be given together")]
+    Incomplete(&'static str, &'static str),
+
+    #[error("The config has no 'synthetic_sky' section")]
+    NoSyntheticSky,
+
+    #[error("Either 'srclist' or 'synthetic_sky' must be given")]
+    NoSkyModel,
+
+    #[error("'gauge: inverse' cannot be used with 'phases', as the phase Fisher matrix is always singular; use 'project', 'reference_antenna' or 'pseudoinverse'")]
+    InverseGauge,
+
+    #[error("The uv range maximum ({max}) must be above the minimum ({min})")]
+    InvalidUvRange { min: f64, max: f64 },
+
+    #[error("'{0}' must be at least 1")]
+    Zero(&'static str),
+
+    #[error("'ionospheric_offsets' needs between 1 and {max} clusters, not {num_clusters}")]
+    OffsetClusters { num_clusters: usize, max: usize },
+
+    #[error(
+        "The synthetic sky flux range {min} to {max} Jy must be positive, finite and increasing"
+    )]
+    InvalidFluxRange { min: f64, max: f64 },
+
+    #[error("'{field}' must be finite and not negative, not {value}")]
+    Negative { field: &'static str, value: f64 },
+
+    #[error("'{field}' must be finite, not {value}")]
+    NotFinite { field: &'static str, value: f64 },
+
+    #[error("'synthetic_sky.clustering.fraction' must be between 0 and 1, not {0}")]
+    InvalidFraction(f64),
+
+    #[error("Cannot draw random numbers for '{field}' from {value}")]
+    Distribution { field: &'static str, value: f64 },
+
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
@@ -0,0 +1,770 @@
+mod error;
+mod observation;
+mod units;
+
+pub use error::*;
+pub use observation::*;
+use units::Quantity;
+
+use crate::calc::ionosphere::MAX_OFFSET_GROUPS;
+use crate::srclist::DEFAULT_SPEC_INDEX;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::{fs, path::Path};
+
+// Struct to store input parameters
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Phase centre [degrees]. Defaults to the metafits phase centre, or
+    /// its pointing centre if it has none.
+    pub ra: Option<f64>,
+    pub dec: Option<f64>,
+
+    /// System temperature [K]
+    #[serde(rename = "T_sys")]
+    pub t_sys: f64,
+
+    /// Station diameter used for the field of view [m]. Defaults to that of
+    /// `telescope`.
+    #[serde(rename = "D")]
+    pub station_diameter: Option<f64>,
+
+    /// Channel width [Hz]. Defaults to the metafits fine channel width.
+    pub channel_width: Option<f64>,
+
+    /// Frequency range of the channels [Hz], if `channels` isn't given.
+    /// Defaults to every coarse channel in the metafits.
+    pub start_freq: Option<f64>,
+    pub end_freq: Option<f64>,
+
+    /// Integration time [s]. Defaults to that of the metafits.
+    pub int_time: Option<f64>,
+
+    /// Local sidereal time [degrees], used to convert hour angles in the
+    /// source list. Defaults to that of the metafits.
+    pub lst: Option<f64>,
+
+    /// The sky model; not needed if `synthetic_sky` is given
+    pub srclist: Option<String>,
+
+    pub metafits: String,
+
+    /// Where each channel's result is written (as JSON lines)
+    #[serde(default = "default_output")]
+    pub output: String,
+
+    #[serde(default)]
+    pub telescope: Telescope,
+
+    /// How to choose the frequency channels. Defaults to `start_freq` up to
+    /// (but not including) `end_freq` in steps of `channel_width`.
+    pub channels: Option<ChannelSpec>,
+
+    /// Fine-channel indices to flag in every coarse channel
+    #[serde(default)]
+    pub flagged_fine_chans: Vec<usize>,
+
+    /// Indices of channels to flag, counted before any flagging
+    #[serde(default)]
+    pub flagged_channels: Vec<usize>,
+
+    /// Optionally fit the gains across all channels with a smooth spectral
+    /// basis, as well as independently in each channel
+    pub spectral_basis: Option<SpectralBasis>,
+
+    /// Also calculate the CRB of a full 2x2 Jones matrix per antenna, using
+    /// all four Stokes parameters of the sky model
+    #[serde(default)]
+    pub full_polarisation: bool,
+
+    /// How the overall-phase degeneracy of the phase Fisher matrix is fixed
+    /// when inverting it. The gain amplitudes have no degeneracy, so their
+    /// Fisher matrix is always inverted directly.
+    #[serde(default)]
+    pub gauge: Gauge,
+
+    /// Print the eigen-spectrum, condition number and sanity checks of the
+    /// Fisher matrix in every channel
+    #[serde(default)]
+    pub diagnostics: bool,
+
+    /// Also calculate the CRB of the gain phases, which always have an
+    /// overall phase degeneracy (fixed with `gauge`)
+    #[serde(default)]
+    pub phases: bool,
+
+    /// Also use the autocorrelations as measurements of the gain amplitudes
+    pub autocorrelations: Option<AutocorrelationConfig>,
+
+    /// Only use baselines in this length range
+    pub uv_range: Option<UvRange>,
+
+    /// Also calculate the CRB of direction-dependent gains, with the sky
+    /// model clustered into this many directions
+    pub num_directions: Option<usize>,
+
+    /// Also estimate an ionospheric position offset for each group of
+    /// components, jointly with the gains. There can be at most 500 groups
+    /// in any channel.
+    pub ionospheric_offsets: Option<OffsetGroups>,
+
+    /// Also treat the Stokes I fluxes of this many of the brightest
+    /// components as unknown, and marginalise the gain CRB over them
+    pub free_fluxes: Option<usize>,
+
+    /// Also calculate the CRB of redundant calibration
+    pub redundant_calibration: Option<RedundantConfig>,
+
+    /// Optionally propagate the gain CRB into a delay power spectrum
+    pub power_spectrum: Option<PowerSpectrumConfig>,
+
+    /// Optionally validate the CRB against simulated calibration
+    pub monte_carlo: Option<MonteCarloConfig>,
+
+    /// Optionally draw a random sky model, used instead of `srclist`
+    pub synthetic_sky: Option<SyntheticSkyConfig>,
+
+    /// Hash of the config as read, after overrides and unit conversion
+    #[serde(skip)]
+    pub hash: String,
+}
+
+// The instrument, which sets the effective area and default station size
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Telescope {
+    #[default]
+    Mwa,
+    Ska,
+}
+
+impl Telescope {
+    /// Effective collecting area of a station [m^2]
+    pub fn effective_area(&self) -> f64 {
+        return match self {
+            Telescope::Mwa => 21.0,
+            Telescope::Ska => (35.0f64 / 2.0).powi(2),
+        };
+    }
+
+    /// Station diameter [m]
+    pub fn diameter(&self) -> f64 {
+        return match self {
+            // A 4x4 grid of dipoles spaced by 1.1 m
+            Telescope::Mwa => 4.4,
+            Telescope::Ska => 35.0,
+        };
+    }
+}
+
+// Where the frequency channels come from
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelSpec {
+    /// All coarse channels in the metafits, split into fine channels of
+    /// `channel_width`
+    Metafits,
+
+    /// The metafits coarse channels with these receiver channel numbers
+    Coarse(Vec<usize>),
+
+    /// Explicit channel centre frequencies [Hz]
+    Freqs(Vec<f64>),
+
+    /// Ranges of channels `[start, end]` [Hz], spaced by `channel_width`
+    Ranges(Vec<[f64; 2]>),
+}
+
+// How to fix the gauge (degenerate directions) of the Fisher matrix
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gauge {
+    /// Invert directly, failing if the matrix is singular
+    Inverse,
+
+    /// Hold this antenna's gain fixed
+    ReferenceAntenna(usize),
+
+    /// Project out the degeneracies known for the parameters (e.g. the
+    /// overall phase)
+    #[default]
+    Project,
+
+    /// Moore-Penrose pseudo-inverse, discarding any (near-)null directions
+    Pseudoinverse,
+}
+
+// Settings for using autocorrelations
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutocorrelationConfig {
+    /// Noise on each (real) autocorrelation [Jy]
+    pub noise: f64,
+}
+
+// Baseline lengths to include in calibration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UvRange {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+
+    #[serde(default)]
+    pub units: LengthUnits,
+}
+
+// Units of baseline lengths
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LengthUnits {
+    #[default]
+    Metres,
+    Wavelengths,
+}
+
+// Basis functions the gains are parameterised with across the band
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpectralBasis {
+    /// Legendre polynomials up to and including this order
+    Polynomial(usize),
+
+    /// A constant plus cosines and sines of up to this many cycles across
+    /// the band
+    Fourier(usize),
+}
+
+// Which components share an ionospheric position offset
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OffsetGroups {
+    /// A separate offset for every component
+    Components,
+
+    /// One offset per cluster, with the sky clustered into this many
+    /// directions
+    Clusters(usize),
+}
+
+// Settings for redundant calibration
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedundantConfig {
+    /// Baselines whose separations differ by less than this are redundant
+    /// [m]
+    #[serde(default = "default_redundancy_tolerance")]
+    pub tolerance: f64,
+}
+
+// Settings for the delay power spectrum of calibration errors
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PowerSpectrumConfig {
+    /// Prefix of the 1D and 2D output files
+    pub output: String,
+
+    #[serde(default = "default_num_kperp_bins")]
+    pub num_kperp_bins: usize,
+
+    /// The foreground model is this many of the brightest components inside
+    /// the field of view at the highest frequency
+    #[serde(default = "default_max_foreground_components")]
+    pub max_components: usize,
+}
+
+// Settings for the Monte Carlo validation of the CRB
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonteCarloConfig {
+    pub num_realisations: usize,
+
+    #[serde(default)]
+    pub seed: u64,
+
+    /// Standard deviation of the simulated gain amplitudes (around 1)
+    #[serde(default = "default_gain_amp_std")]
+    pub gain_amp_std: f64,
+
+    /// Standard deviation of the simulated gain phases [degrees]
+    #[serde(default = "default_gain_phase_std")]
+    pub gain_phase_std: f64,
+}
+
+// Settings for drawing a synthetic sky model from dN/dS = norm * S^-slope
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyntheticSkyConfig {
+    /// Where to write the generated source list
+    pub output: String,
+
+    #[serde(default)]
+    pub seed: u64,
+
+    /// Differential source counts at 1 Jy [Jy^-1 sr^-1]
+    pub norm: f64,
+
+    /// Power-law slope of the differential source counts
+    pub slope: f64,
+
+    /// Flux density range [Jy]
+    pub flux_min: f64,
+    pub flux_max: f64,
+
+    /// Frequency the source counts and flux densities refer to [Hz]
+    pub freq: f64,
+
+    #[serde(default = "default_spectral_index_mean")]
+    pub spectral_index_mean: f64,
+
+    #[serde(default = "default_spectral_index_std")]
+    pub spectral_index_std: f64,
+
+    /// Radius of the sky cap around the phase centre [degrees]. The whole
+    /// sky is used if this is not given.
+    pub radius: Option<f64>,
+
+    pub clustering: Option<ClusteringConfig>,
+}
+
+// Settings for scattering some of the synthetic sources around cluster centres
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusteringConfig {
+    /// Fraction of sources that belong to clusters
+    pub fraction: f64,
+
+    /// Gaussian scatter of members about their cluster centre [degrees]
+    pub radius: f64,
+
+    /// Mean number of sources per cluster
+    pub mean_members: f64,
+}
+
+fn default_spectral_index_mean() -> f64 {
+    return DEFAULT_SPEC_INDEX;
+}
+
+fn default_spectral_index_std() -> f64 {
+    return 0.2;
+}
+
+fn default_redundancy_tolerance() -> f64 {
+    return 0.1;
+}
+
+fn default_output() -> String {
+    return "crb_results.jsonl".to_string();
+}
+
+fn default_num_kperp_bins() -> usize {
+    return 20;
+}
+
+fn default_max_foreground_components() -> usize {
+    return 500;
+}
+
+fn default_gain_amp_std() -> f64 {
+    return 0.1;
+}
+
+fn default_gain_phase_std() -> f64 {
+    return 10.0;
+}
+
+// Fields (nested keys separated by dots) that may be given with units, and
+// the quantity each is. Lists are converted element by element.
+const QUANTITIES: &[(&str, Quantity)] = &[
+    ("ra", Quantity::RightAscension),
+    ("dec", Quantity::Angle),
+    ("T_sys", Quantity::Temperature),
+    ("D", Quantity::Length),
+    ("channel_width", Quantity::Frequency),
+    ("start_freq", Quantity::Frequency),
+    ("end_freq", Quantity::Frequency),
+    ("int_time", Quantity::Time),
+    ("lst", Quantity::RightAscension),
+    ("channels.freqs", Quantity::Frequency),
+    ("channels.ranges", Quantity::Frequency),
+    ("redundant_calibration.tolerance", Quantity::Length),
+    ("monte_carlo.gain_phase_std", Quantity::Angle),
+    ("synthetic_sky.freq", Quantity::Frequency),
+    ("synthetic_sky.radius", Quantity::Angle),
+    ("synthetic_sky.clustering.radius", Quantity::Angle),
+];
+
+impl Config {
+    // Read in input parameters for config YAML file, then apply any
+    // `key=value` overrides (nested keys are separated by dots), convert any
+    // values given with units and check the result
+    pub fn read_config<P: AsRef<Path>>(path: P, overrides: &[String]) -> Result<Self, ConfigError> {
+        let file_str: String = fs::read_to_string(path)?;
+        let mut yaml: Value = serde_yaml::from_str(&file_str)?;
+        for spec in overrides {
+            apply_override(&mut yaml, spec)?;
+        }
+        for &(key, quantity) in QUANTITIES {
+            if let Some(value) = lookup_mut(&mut yaml, key) {
+                convert_units(value, key, quantity)?;
+            }
+        }
+        let hash = fnv1a(serde_yaml::to_string(&yaml)?.as_bytes());
+        let mut config: Config = serde_yaml::from_value(yaml)?;
+        config.hash = format!("{:016x}", hash);
+        config.validate()?;
+        return Ok(config);
+    }
+
+    /// Station diameter [m]
+    pub fn diameter(&self) -> f64 {
+        return self.station_diameter.unwrap_or(self.telescope.diameter());
+    }
+
+    /// Complain about values that can't be right.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let positive = [
+            ("T_sys", Some(self.t_sys)),
+            ("D", Some(self.diameter())),
+            ("channel_width", self.channel_width),
+            ("start_freq", self.start_freq),
+            ("int_time", self.int_time),
+            (
+                "autocorrelations.noise",
+                self.autocorrelations.as_ref().map(|auto| auto.noise),
+            ),
+        ];
+        for (field, value) in positive {
+            if let Some(value) = value {
+                if value.is_nan() || value <= 0.0 {
+                    return Err(ConfigError::NotPositive { field, value });
+                }
+            }
+        }
+
+        match (self.start_freq, self.end_freq) {
+            (Some(start), Some(end)) if end <= start => {
+                return Err(ConfigError::InvalidFrequencyRange { start, end });
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(ConfigError::Incomplete("start_freq", "end_freq"));
+            }
+            _ => (),
+        }
+        match (self.ra, self.dec) {
+            (Some(ra), _) if !(0.0..360.0).contains(&ra) => {
+                return Err(ConfigError::InvalidRa(ra));
+            }
+            (_, Some(dec)) if !(-90.0..=90.0).contains(&dec) => {
+                return Err(ConfigError::InvalidDec(dec));
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(ConfigError::Incomplete("ra", "dec"));
+            }
+            _ => (),
+        }
+
+        if self.srclist.is_none() && self.synthetic_sky.is_none() {
+            return Err(ConfigError::NoSkyModel);
+        }
+
+        if self.phases && matches!(self.gauge, Gauge::Inverse) {
+            return Err(ConfigError::InverseGauge);
+        }
+
+        if let Some(UvRange {
+            min: Some(min),
+            max: Some(max),
+            ..
+        }) = self.uv_range
+        {
+            if max <= min {
+                return Err(ConfigError::InvalidUvRange { min, max });
+            }
+        }
+
+        let counts = [
+            ("num_directions", self.num_directions),
+            ("free_fluxes", self.free_fluxes),
+            (
+                "monte_carlo.num_realisations",
+                self.monte_carlo.as_ref().map(|mc| mc.num_realisations),
+            ),
+            (
+                "power_spectrum.num_kperp_bins",
+                self.power_spectrum.as_ref().map(|ps| ps.num_kperp_bins),
+            ),
+        ];
+        for (field, count) in counts {
+            if count == Some(0) {
+                return Err(ConfigError::Zero(field));
+            }
+        }
+
+        if let Some(OffsetGroups::Clusters(num_clusters)) = self.ionospheric_offsets {
+            if !(1..=MAX_OFFSET_GROUPS).contains(&num_clusters) {
+                return Err(ConfigError::OffsetClusters {
+                    num_clusters,
+                    max: MAX_OFFSET_GROUPS,
+                });
+            }
+        }
+
+        if let Some(sky) = &self.synthetic_sky {
+            if !(sky.flux_min > 0.0 && sky.flux_max > sky.flux_min && sky.flux_max.is_finite()) {
+                return Err(ConfigError::InvalidFluxRange {
+                    min: sky.flux_min,
+                    max: sky.flux_max,
+                });
+            }
+            if sky.freq.is_nan() || sky.freq <= 0.0 {
+                return Err(ConfigError::NotPositive {
+                    field: "synthetic_sky.freq",
+                    value: sky.freq,
+                });
+            }
+            let finite = [
+                ("synthetic_sky.slope", sky.slope),
+                ("synthetic_sky.spectral_index_mean", sky.spectral_index_mean),
+            ];
+            for (field, value) in finite {
+                if !value.is_finite() {
+                    return Err(ConfigError::NotFinite { field, value });
+                }
+            }
+            if let Some(radius) = sky.radius {
+                if radius.is_nan() || radius <= 0.0 {
+                    return Err(ConfigError::NotPositive {
+                        field: "synthetic_sky.radius",
+                        value: radius,
+                    });
+                }
+            }
+            if let Some(clustering) = &sky.clustering {
+                if !(0.0..=1.0).contains(&clustering.fraction) {
+                    return Err(ConfigError::InvalidFraction(clustering.fraction));
+                }
+                if clustering.mean_members.is_nan() || clustering.mean_members <= 0.0 {
+                    return Err(ConfigError::NotPositive {
+                        field: "synthetic_sky.clustering.mean_members",
+                        value: clustering.mean_members,
+                    });
+                }
+            }
+        }
+
+        let non_negative = [
+            (
+                "synthetic_sky.norm",
+                self.synthetic_sky.as_ref().map(|sky| sky.norm),
+            ),
+            (
+                "synthetic_sky.spectral_index_std",
+                self.synthetic_sky
+                    .as_ref()
+                    .map(|sky| sky.spectral_index_std),
+            ),
+            (
+                "synthetic_sky.clustering.radius",
+                self.synthetic_sky
+                    .as_ref()
+                    .and_then(|sky| sky.clustering.as_ref())
+                    .map(|clustering| clustering.radius),
+            ),
+            (
+                "monte_carlo.gain_amp_std",
+                self.monte_carlo.as_ref().map(|mc| mc.gain_amp_std),
+            ),
+            (
+                "monte_carlo.gain_phase_std",
+                self.monte_carlo.as_ref().map(|mc| mc.gain_phase_std),
+            ),
+        ];
+        for (field, value) in non_negative {
+            if let Some(value) = value {
+                if !(value.is_finite() && value >= 0.0) {
+                    return Err(ConfigError::Negative { field, value });
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// 64-bit FNV-1a hash, which (unlike the standard library's hasher) is the
+/// same across Rust versions, so results can be matched to their config.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    return bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+}
+
+/// The value at a (possibly nested) key, if it is present. Keys can also
+/// match the tag of an enum variant, e.g. `channels: !freqs [...]`.
+fn lookup_mut<'a>(yaml: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    let mut node = yaml;
+    for part in key.split('.') {
+        node = match node {
+            Value::Mapping(mapping) => mapping.get_mut(part)?,
+            Value::Tagged(tagged) if tagged.tag == part => &mut tagged.value,
+            _ => return None,
+        };
+    }
+    return Some(node);
+}
+
+/// Replace any strings with units in `value` (and its elements, if it is a
+/// list) by numbers in the base unit of `quantity`.
+fn convert_units(value: &mut Value, key: &str, quantity: Quantity) -> Result<(), ConfigError> {
+    match value {
+        Value::String(s) => {
+            let converted = quantity
+                .parse(s)
+                .ok_or_else(|| ConfigError::InvalidQuantity {
+                    key: key.to_string(),
+                    value: s.clone(),
+                    expected: quantity.describe(),
+                })?;
+            *value = Value::from(converted);
+        }
+        Value::Sequence(values) => {
+            for value in values.iter_mut() {
+                convert_units(value, key, quantity)?;
+            }
+        }
+        _ => (),
+    }
+    return Ok(());
+}
+
+/// Set the (possibly nested) key of `spec = "a.b.c=value"` in `yaml`, creating
+/// any mappings that don't exist yet. The value is parsed as YAML, so numbers,
+/// lists and mappings can all be given.
+fn apply_override(yaml: &mut Value, spec: &str) -> Result<(), ConfigError> {
+    let (key, value) = spec
+        .split_once('=')
+        .ok_or_else(|| ConfigError::MalformedOverride(spec.to_string()))?;
+    if key.is_empty() {
+        return Err(ConfigError::MalformedOverride(spec.to_string()));
+    }
+    let value: Value = serde_yaml::from_str(value)?;
+
+    let mut node = yaml;
+    let mut parent = String::new();
+    for part in key.split('.') {
+        if node.is_null() {
+            *node = Value::Mapping(Mapping::new());
+        }
+        let mapping = node
+            .as_mapping_mut()
+            .ok_or_else(|| ConfigError::OverrideNotMapping {
+                key: key.to_string(),
+                parent: parent.clone(),
+            })?;
+        node = mapping
+            .entry(Value::String(part.to_string()))
+            .or_insert(Value::Null);
+        parent = if parent.is_empty() {
+            part.to_string()
+        } else {
+            format!("{parent}.{part}")
+        };
+    }
+    *node = value;
+
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write a config to a fresh file in the temporary directory.
+    fn config_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("crb_{}_{name}.yaml", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        return path;
+    }
+
+    const MINIMAL: &str = "T_sys: 200\nmetafits: obs.metafits\nsrclist: sky.yaml\n";
+
+    #[test]
+    fn nested_overrides() {
+        let mut yaml: Value = serde_yaml::from_str("a: {b: 1}\nc: 2").unwrap();
+        apply_override(&mut yaml, "a.b=3").unwrap();
+        apply_override(&mut yaml, "d.e.f=[1, 2]").unwrap();
+        let expected: Value = serde_yaml::from_str("a: {b: 3}\nc: 2\nd: {e: {f: [1, 2]}}").unwrap();
+        assert_eq!(yaml, expected);
+
+        assert!(matches!(
+            apply_override(&mut yaml, "c.x=1"),
+            Err(ConfigError::OverrideNotMapping { .. })
+        ));
+        assert!(matches!(
+            apply_override(&mut yaml, "no_value"),
+            Err(ConfigError::MalformedOverride(_))
+        ));
+        assert!(matches!(
+            apply_override(&mut yaml, "=1"),
+            Err(ConfigError::MalformedOverride(_))
+        ));
+    }
+
+    #[test]
+    fn overrides_take_units_and_are_validated() {
+        let path = config_file("overrides", MINIMAL);
+        let overrides = ["channel_width=40 kHz".to_string(), "D=6".to_string()];
+        let config = Config::read_config(&path, &overrides).unwrap();
+        assert_eq!(config.channel_width, Some(40e3));
+        assert_eq!(config.diameter(), 6.0);
+
+        let plain = Config::read_config(&path, &[]).unwrap();
+        assert_ne!(config.hash, plain.hash);
+
+        let result = Config::read_config(&path, &["T_sys=-1".to_string()]);
+        assert!(matches!(result, Err(ConfigError::NotPositive { .. })));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let path = config_file("unknown", &format!("{MINIMAL}tsys: 100\n"));
+        assert!(Config::read_config(&path, &[]).is_err());
+        fs::remove_file(&path).unwrap();
+
+        let path = config_file("unknown_override", MINIMAL);
+        assert!(Config::read_config(&path, &["uv_range.maximum=100".to_string()]).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn observation_values_may_come_from_the_metafits() {
+        let path = config_file("metafits_defaults", MINIMAL);
+        let config = Config::read_config(&path, &[]).unwrap();
+        assert!(config.ra.is_none() && config.dec.is_none());
+        assert!(config.channel_width.is_none() && config.int_time.is_none());
+        assert!(config.start_freq.is_none() && config.lst.is_none());
+
+        // Values that only make sense together must be given together.
+        let config = Config::read_config(&path, &["ra=10".to_string()]);
+        assert!(matches!(config, Err(ConfigError::Incomplete("ra", "dec"))));
+        let config = Config::read_config(&path, &["start_freq=150 MHz".to_string()]);
+        assert!(matches!(
+            config,
+            Err(ConfigError::Incomplete("start_freq", "end_freq"))
+        ));
+        let overrides = [
+            "start_freq=150 MHz".to_string(),
+            "end_freq=160 MHz".to_string(),
+            "lst=01:00:00".to_string(),
+        ];
+        let config = Config::read_config(&path, &overrides).unwrap();
+        assert_eq!(
+            (config.start_freq, config.end_freq),
+            (Some(150e6), Some(160e6))
+        );
+        assert_eq!(config.lst, Some(15.0));
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+//! Observation parameters that can be left out of the config and taken from
+//! the metafits instead.
+
+use log::info;
+use marlu::RADec;
+use mwalib::MetafitsContext;
+
+use super::Config;
+
+/// The parameters of the observation, from the config where given and from
+/// the metafits otherwise.
+pub struct Observation {
+    pub phase_centre: RADec,
+
+    /// Width of each channel [Hz]
+    pub channel_width: f64,
+
+    /// Integration time [s]
+    pub int_time: f64,
+
+    /// Local sidereal time [degrees], used to convert hour angles
+    pub lst: f64,
+}
+
+impl Observation {
+    /// Fill in anything missing from `config` with `metafits`, logging where
+    /// each value came from.
+    pub fn new(config: &Config, metafits: &MetafitsContext) -> Self {
+        let (ra, dec, source) = match (config.ra, config.dec) {
+            (Some(ra), Some(dec)) => (ra, dec, "config"),
+            _ => match (
+                metafits.ra_phase_center_degrees,
+                metafits.dec_phase_center_degrees,
+            ) {
+                (Some(ra), Some(dec)) => (ra, dec, "metafits phase centre"),
+                _ => (
+                    metafits.ra_tile_pointing_degrees,
+                    metafits.dec_tile_pointing_degrees,
+                    "metafits pointing centre",
+                ),
+            },
+        };
+        info!("Phase centre: RA {} deg, Dec {} deg ({})", ra, dec, source);
+
+        let (channel_width, source) = match config.channel_width {
+            Some(channel_width) => (channel_width, "config"),
+            None => (
+                metafits.corr_fine_chan_width_hz as f64,
+                "metafits fine channel width",
+            ),
+        };
+        info!("Channel width: {} Hz ({})", channel_width, source);
+
+        let (int_time, source) = match config.int_time {
+            Some(int_time) => (int_time, "config"),
+            None => (
+                metafits.corr_int_time_ms as f64 / 1e3,
+                "metafits integration time",
+            ),
+        };
+        info!("Integration time: {} s ({})", int_time, source);
+
+        let (lst, source) = match config.lst {
+            Some(lst) => (lst, "config"),
+            None => (metafits.lst_deg, "metafits"),
+        };
+        info!("LST: {} deg ({})", lst, source);
+
+        if config.channels.is_none() && config.start_freq.is_none() {
+            info!(
+                "Channels: {} metafits coarse channels",
+                metafits.metafits_coarse_chans.len()
+            );
+        }
+
+        return Self {
+            phase_centre: RADec::from_degrees(ra, dec),
+            channel_width,
+            int_time,
+            lst,
+        };
+    }
+}
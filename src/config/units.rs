@@ -0,0 +1,179 @@
+//! Physical quantities in the config, which can be given as plain numbers (in
+//! the base unit) or as strings with a unit suffix, e.g. `"150 MHz"`, `"8 s"`
+//! or `"10h20m"`.
+
+use crate::srclist::read;
+
+/// The kinds of quantity that accept a unit suffix.
+#[derive(Clone, Copy, Debug)]
+pub enum Quantity {
+    /// Base unit Hz
+    Frequency,
+
+    /// Base unit s
+    Time,
+
+    /// Base unit degrees; also accepts sexagesimal `"10h20m30s"`,
+    /// `"-27d30m"` and `"-27:30:00"` (degrees)
+    Angle,
+
+    /// As `Angle`, but `"10:20:30"` is in hours
+    RightAscension,
+
+    /// Base unit m
+    Length,
+
+    /// Base unit K
+    Temperature,
+}
+
+impl Quantity {
+    /// The units accepted for this quantity, and the factor converting each
+    /// to the base unit.
+    fn units(&self) -> &'static [(&'static str, f64)] {
+        return match self {
+            Quantity::Frequency => &[("Hz", 1.0), ("kHz", 1e3), ("MHz", 1e6), ("GHz", 1e9)],
+            Quantity::Time => &[("s", 1.0), ("ms", 1e-3), ("min", 60.0), ("h", 3600.0)],
+            Quantity::Angle | Quantity::RightAscension => &[
+                ("deg", 1.0),
+                ("d", 1.0),
+                ("rad", 180.0 / std::f64::consts::PI),
+                ("arcmin", 1.0 / 60.0),
+                ("arcsec", 1.0 / 3600.0),
+                ("h", 15.0),
+            ],
+            Quantity::Length => &[("m", 1.0), ("cm", 1e-2), ("km", 1e3)],
+            Quantity::Temperature => &[("K", 1.0)],
+        };
+    }
+
+    /// A description of the accepted units, for error messages.
+    pub fn describe(&self) -> String {
+        let units: Vec<&str> = self.units().iter().map(|(unit, _)| *unit).collect();
+        return format!("{:?} in {}", self, units.join(", "));
+    }
+
+    /// Convert a string like `"150 MHz"` to the base unit. A number without a
+    /// unit is already in the base unit.
+    pub fn parse(&self, s: &str) -> Option<f64> {
+        if s.contains(':') {
+            return match self {
+                Quantity::Angle => read::parse_dec(s).ok(),
+                Quantity::RightAscension => read::parse_ra(s).ok(),
+                _ => None,
+            };
+        }
+
+        let terms = split_terms(s)?;
+        if let [(value, unit)] = terms.as_slice() {
+            if unit.is_empty() {
+                return Some(*value);
+            }
+            let factor = self.units().iter().find(|(u, _)| u == unit)?.1;
+            return Some(value * factor);
+        }
+
+        // Otherwise, only sexagesimal angles have several terms.
+        if !matches!(self, Quantity::Angle | Quantity::RightAscension) {
+            return None;
+        }
+        let (first, unit) = terms[0];
+        let scale = match unit {
+            "h" => 15.0,
+            "d" => 1.0,
+            _ => return None,
+        };
+        let expected = ["m", "s"];
+        if terms.len() > expected.len() + 1 {
+            return None;
+        }
+        let mut total = first.abs();
+        for (&(value, unit), (expected, divisor)) in
+            terms[1..].iter().zip(expected.iter().zip([60.0, 3600.0]))
+        {
+            if unit != *expected || value < 0.0 {
+                return None;
+            }
+            total += value / divisor;
+        }
+        let sign = if s.trim_start().starts_with('-') {
+            -1.0
+        } else {
+            1.0
+        };
+        return Some(sign * scale * total);
+    }
+}
+
+/// Split e.g. `"10h20m"` into `[(10, "h"), (20, "m")]`, allowing whitespace
+/// between a number and its unit.
+fn split_terms(s: &str) -> Option<Vec<(f64, &str)>> {
+    let mut terms = vec![];
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let number_len = number_len(rest);
+        let value: f64 = rest[..number_len].parse().ok()?;
+        rest = rest[number_len..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        terms.push((value, &rest[..unit_len]));
+        rest = rest[unit_len..].trim_start();
+    }
+
+    if terms.is_empty() {
+        return None;
+    }
+    return Some(terms);
+}
+
+/// The length of the number at the start of `s`: an optional sign, digits
+/// and a decimal point, and an exponent only if digits follow it (so that
+/// e.g. the unit in `"1e"` isn't mistaken for one).
+fn number_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let digits = |start: usize| {
+        start
+            + bytes[start..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit() || **b == b'.')
+                .count()
+    };
+
+    let sign = usize::from(matches!(bytes.first(), Some(b'+' | b'-')));
+    let mantissa = digits(sign);
+    if !matches!(bytes.get(mantissa), Some(b'e' | b'E')) {
+        return mantissa;
+    }
+    let exponent_sign =
+        mantissa + 1 + usize::from(matches!(bytes.get(mantissa + 1), Some(b'+' | b'-')));
+    let exponent = digits(exponent_sign);
+    if exponent == exponent_sign {
+        return mantissa;
+    }
+    return exponent;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negative_sexagesimal_below_one_unit() {
+        assert_eq!(Quantity::Angle.parse("-00:30:00"), Some(-0.5));
+        assert_eq!(Quantity::Angle.parse("-00d30m"), Some(-0.5));
+        assert_eq!(Quantity::RightAscension.parse("-00:30:00"), Some(-7.5));
+        assert_eq!(Quantity::RightAscension.parse("-00h30m"), Some(-7.5));
+        assert_eq!(Quantity::Angle.parse("-27:30:00"), Some(-27.5));
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(Quantity::Frequency.parse("150 MHz"), Some(150e6));
+        assert_eq!(Quantity::Time.parse("8s"), Some(8.0));
+        assert_eq!(Quantity::RightAscension.parse("10h20m"), Some(155.0));
+        assert_eq!(Quantity::Frequency.parse("1e"), None);
+        assert_eq!(Quantity::Length.parse("3 MHz"), None);
+    }
+}
@@ -0,0 +1,38 @@
+use thiserror::Error;
+
+use crate::calc::CalcError;
+use crate::channels::ChannelError;
+use crate::config::ConfigError;
+use crate::output::OutputError;
+use crate::srclist::{ReadSourceListError, WriteSourceListError};
+
+/// Any error from the library.
+#[derive(Error, Debug)]
+pub enum CrbError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    ReadSourceList(#[from] ReadSourceListError),
+
+    #[error(transparent)]
+    WriteSourceList(#[from] WriteSourceListError),
+
+    #[error(transparent)]
+    Channel(#[from] ChannelError),
+
+    #[error(transparent)]
+    Calc(#[from] CalcError),
+
+    #[error(transparent)]
+    Output(#[from] OutputError),
+
+    #[error(transparent)]
+    Metafits(#[from] mwalib::MwalibError),
+
+    #[error(transparent)]
+    Shape(#[from] ndarray::ShapeError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
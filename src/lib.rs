@@ -0,0 +1,27 @@
+//! Cramér-Rao bounds on the calibration errors of radio interferometers.
+//!
+//! The library covers reading and writing sky models ([`srclist`]),
+//! selecting components ([`srclist::ComponentList`]), building baselines and
+//! Fisher matrices and inverting them into CRBs ([`calc`]), and propagating
+//! the results into delay power spectra ([`power`]). [`run::run_crb`] is the
+//! whole calculation driven by a [`config::Config`], as used by the binary.
+//! With the `python` feature, the main parts are also available as a Python
+//! module.
+
+#![allow(clippy::needless_return)]
+
+pub mod calc;
+pub mod channels;
+pub mod config;
+mod error;
+pub mod logging;
+pub mod output;
+pub mod power;
+#[cfg(feature = "python")]
+mod python;
+pub mod report;
+pub mod run;
+pub mod simulate;
+pub mod srclist;
+
+pub use error::*;
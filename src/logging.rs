@@ -0,0 +1,103 @@
+//! Leveled logging to stderr, either as text or as JSON lines for batch jobs,
+//! and progress bars that log messages are printed around. Without [`init`]
+//! (e.g. when used as a library), nothing is logged and progress bars are
+//! hidden.
+
+use std::io::Write;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+static PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Targets of the library and the binary, the only ones logged below `Info`.
+const OWN_CRATES: [&str; 2] = ["crb", env!("CARGO_PKG_NAME")];
+
+/// How each log message is written.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum LogFormat {
+    /// `LEVEL message`, with progress bars on a terminal
+    #[default]
+    Text,
+
+    /// One JSON object per message, with the time, level, module and
+    /// message; progress bars are hidden
+    Json,
+}
+
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only our own debugging output; dependencies can be noisy.
+        let krate = metadata.target().split("::").next().unwrap_or_default();
+        return metadata.level() <= self.level
+            && (metadata.level() <= log::Level::Info || OWN_CRATES.contains(&krate));
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = match self.format {
+            LogFormat::Text => format!("{:<5} {}", record.level(), record.args()),
+            LogFormat::Json => {
+                let time = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0.0, |t| t.as_secs_f64());
+                serde_json::json!({
+                    "time": time,
+                    "level": record.level().as_str(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                })
+                .to_string()
+            }
+        };
+        let print = || {
+            let _ = writeln!(std::io::stderr(), "{}", line);
+        };
+        match PROGRESS.get() {
+            Some(progress) => progress.suspend(print),
+            None => print(),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Start logging messages up to `level`. Progress bars are shown only for
+/// text output at `Info` or more verbose, and only on a terminal.
+pub fn init(level: LevelFilter, format: LogFormat) -> Result<(), SetLoggerError> {
+    let target = match format {
+        LogFormat::Text if level >= LevelFilter::Info => ProgressDrawTarget::stderr(),
+        _ => ProgressDrawTarget::hidden(),
+    };
+    let _ = PROGRESS.set(MultiProgress::with_draw_target(target));
+    log::set_boxed_logger(Box::new(Logger { level, format }))?;
+    log::set_max_level(level);
+    return Ok(());
+}
+
+/// A progress bar of `len` steps with an ETA, hidden unless logging has been
+/// set up for it.
+pub fn progress_bar(len: usize, message: &'static str) -> ProgressBar {
+    let Some(progress) = PROGRESS.get() else {
+        return ProgressBar::hidden();
+    };
+    let style = ProgressStyle::with_template(
+        "{msg:>10} [{bar:40}] {pos}/{len} ({elapsed_precise}, ETA {eta_precise})",
+    )
+    .unwrap()
+    .progress_chars("=> ");
+    return progress.add(
+        ProgressBar::new(len as u64)
+            .with_style(style)
+            .with_message(message),
+    );
+}
@@ -1,132 +1,178 @@
-mod calc;
-mod srclist;
-use calc::*;
-use marlu::RADec;
-use std::env;
-use std::error::Error;
-use std::io;
-mod config;
-use crate::config::Config;
-use crate::srclist::read;
-use mwalib::MetafitsContext;
-use ndarray::prelude::*;
-use physical_constants;
-use srclist::*;
-use std::fs;
-use std::time::Instant;
+#![allow(clippy::needless_return)]
 
-fn create_baselines(metafits: &String) -> Result<Array<f64, Dim<[usize; 3]>>, Box<dyn Error>> {
-    let file = MetafitsContext::new(metafits, None)?;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-    let mut baselines_xy = Array::<f64, _>::zeros((128, 128, 2));
+use clap::{ArgAction, Parser, Subcommand};
+use log::{debug, info, LevelFilter};
+use mwalib::MetafitsContext;
 
-    for (i, ant_i) in file.antennas.iter().enumerate() {
-        for (j, ant_j) in file.antennas.iter().enumerate() {
-            baselines_xy[[i, j, 0]] = ant_i.east_m - ant_j.east_m;
-            baselines_xy[[i, j, 1]] = ant_i.north_m - ant_j.north_m;
-        }
-    }
-    return Ok(baselines_xy);
+use crb::config::{Config, ConfigError, Observation};
+use crb::logging::{self, LogFormat};
+use crb::srclist::{generate, read, write};
+use crb::{report, run, CrbError};
+
+/// Cramér-Rao bounds on calibration errors for radio interferometers.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Log more detail; give twice for trace output
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Only log warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Format of the log lines on stderr
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
 }
 
-fn calc_rms(T_sys: f64, bandwith: f64, int_time: f64, telescope: &String) -> f64 {
-    let mut A_eff: f64 = 0.0;
+#[derive(Subcommand)]
+enum Command {
+    /// Calculate the CRB of the antenna gains in every channel.
+    Crb {
+        /// Path to the yaml config
+        config: PathBuf,
+
+        /// Override a config field, e.g. `--set monte_carlo.num_realisations=100`
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        overrides: Vec<String>,
+
+        /// Skip channels already in the output, which must have been written
+        /// with the same config
+        #[arg(long)]
+        resume: bool,
+    },
+
+    /// Convert a source list between yaml and json, based on the file
+    /// extensions.
+    Convert {
+        /// Source list to read
+        input: PathBuf,
+
+        /// Source list to write
+        output: PathBuf,
+
+        /// Metafits of the observation, whose LST converts any hour angles
+        #[arg(long)]
+        metafits: Option<PathBuf>,
+    },
+
+    /// Check a source list for problems and summarise its contents.
+    ValidateSrclist {
+        /// Source list to check
+        srclist: PathBuf,
+
+        /// Metafits of the observation, whose LST converts any hour angles
+        #[arg(long)]
+        metafits: Option<PathBuf>,
+    },
+
+    /// Generate the synthetic sky described in a config, without
+    /// calculating anything.
+    Simulate {
+        /// Path to the yaml config
+        config: PathBuf,
+
+        /// Override a config field, e.g. `--set synthetic_sky.seed=2`
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        overrides: Vec<String>,
+    },
+
+    /// Summarise an observation's metafits file.
+    Info {
+        /// Path to the metafits file
+        metafits: PathBuf,
+    },
+}
 
-    if telescope == "mwa" {
-        A_eff = 21.0;
-    } else if telescope == "ska" {
-        A_eff = (35.0f64 / 2.0f64).powi(2);
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    if let Err(e) = logging::init(level, cli.log_format) {
+        eprintln!("Error: {e}");
+        return ExitCode::FAILURE;
     }
 
-    let k = physical_constants::BOLTZMANN_CONSTANT;
-
-    return 10.0f64.powi(26) * (2.0 * k * T_sys) / (A_eff * (bandwith * int_time).sqrt());
+    if let Err(e) = run(cli.command) {
+        log::error!("{e}");
+        return ExitCode::FAILURE;
+    }
+    return ExitCode::SUCCESS;
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
-    println!("CONFIG FILE: {}", &args[1]);
-
-    let config: Config = Config::read_config(&args[1])?;
-    println!("YAML: {:?}", config);
-
-    let file = fs::File::open(&config.srclist)?;
-    let mut buf_reader = io::BufReader::new(file);
-    let source_list: SourceList = read::source_list_from_yaml(&mut buf_reader)?;
-
-    let mut component_list: ComponentList = ComponentList::new(source_list);
-
-    println!(
-        "Number of components before veto: {}",
-        &component_list.len()
-    );
-
-    let rms_re = calc_rms(
-        config.T_sys,
-        config.channel_width,
-        config.int_time,
-        &config.telescope,
-    );
-
-    let rms_vis: f64 = 5.0 * (rms_re / 8256.0f64.sqrt());
-    component_list.veto_by_flux(rms_vis);
-
-    println!(
-        "Number of components after flux {} veto: {}",
-        rms_vis,
-        &component_list.len()
-    );
-
-    // Create phase centre from inputs
-    let phase_centre = RADec::from_degrees(config.ra, config.dec);
-    println!("phase centre: {:?}", phase_centre);
-
-    // for comp in component_list.iter() {
-    //     let lmn = comp.radec.to_lmn(phase_centre);
-    //     println!(
-    //         "ra: {}, dec: {}, l: {}, m: {}, dist: {}",
-    //         comp.radec.ra,
-    //         comp.radec.dec,
-    //         lmn.l,
-    //         lmn.m,
-    //         (lmn.l.powi(2) + lmn.m.powi(2)).sqrt()
-    //     );
-    // }
-
-    let baselines_xy = create_baselines(&config.metafits)?;
-    // println!("{:?}", baselines_xy);
-
-    let num_freq: usize =
-        ((config.end_freq - config.start_freq) / config.channel_width).floor() as usize;
-
-    println!("NUM FREQ: {}", num_freq);
-    let freq_array = Array::linspace(config.start_freq, config.end_freq, num_freq);
-
-    for freq in freq_array.iter() {
-        let start_time = Instant::now();
-        println!("==================== FREQ: {} ====================", freq);
-
-        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / freq;
-        let mut freq_comp_list = component_list.clone();
-        freq_comp_list.veto_by_fov(phase_centre, lambda, config.D);
-        println!(
-            "Number of components after veto: {}",
-            &freq_comp_list.slice_to_struct(0..500).len()
-        );
-
-        println!("Calculating CRB");
-        calc::calculate_crb(
-            &baselines_xy,
-            freq_comp_list.slice_to_struct(0..500),
-            lambda,
-            *freq,
-            rms_vis,
-            phase_centre,
-        );
-
-        let end_time = start_time.elapsed();
-        println!("This block took: {:?}", end_time);
+fn run(command: Command) -> Result<(), CrbError> {
+    match command {
+        Command::Crb {
+            config,
+            overrides,
+            resume,
+        } => {
+            info!("Config file: {}", config.display());
+            let config = Config::read_config(&config, &overrides)?;
+            debug!("Config: {:?}", config);
+            run::run_crb(&config, resume)?;
+        }
+        Command::Convert {
+            input,
+            output,
+            metafits,
+        } => {
+            let source_list = read::source_list_from_file(&input, metafits_lst(metafits)?)?;
+            write::source_list_to_file(&output, &source_list)?;
+            info!(
+                "Converted {} sources from {} to {}",
+                source_list.len(),
+                input.display(),
+                output.display()
+            );
+        }
+        Command::ValidateSrclist { srclist, metafits } => {
+            let source_list = read::source_list_from_file(&srclist, metafits_lst(metafits)?)?;
+            read::check_source_list(&source_list)?;
+            report::summarise_source_list(&mut io::stdout(), &source_list)?;
+        }
+        Command::Simulate { config, overrides } => {
+            let config = Config::read_config(&config, &overrides)?;
+            let synthetic_sky = config
+                .synthetic_sky
+                .as_ref()
+                .ok_or(ConfigError::NoSyntheticSky)?;
+            let metafits = MetafitsContext::new(&config.metafits, None)?;
+            let observation = Observation::new(&config, &metafits);
+            let source_list =
+                generate::generate_source_list(synthetic_sky, observation.phase_centre)?;
+            write::source_list_to_file(&synthetic_sky.output, &source_list)?;
+            info!(
+                "Generated {} synthetic sources, written to {}",
+                source_list.len(),
+                synthetic_sky.output
+            );
+        }
+        Command::Info { metafits } => {
+            let metafits = MetafitsContext::new(&metafits, None)?;
+            report::summarise_metafits(&mut io::stdout(), &metafits)?;
+        }
     }
 
     return Ok(());
 }
+
+/// The LST of the observation in a metafits file [degrees], if one is given.
+fn metafits_lst(metafits: Option<PathBuf>) -> Result<Option<f64>, mwalib::MwalibError> {
+    return match metafits {
+        Some(metafits) => Ok(Some(MetafitsContext::new(metafits, None)?.lst_deg)),
+        None => Ok(None),
+    };
+}
@@ -0,0 +1,29 @@
+use thiserror::Error;
+
+/// Errors associated with writing or resuming the per-channel results.
+#[derive(Error, Debug)]
+pub enum OutputError {
+    #[error("{path} was written with a different config (hash {found}, expected {expected}); remove it or run without --resume")]
+    ConfigMismatch {
+        path: String,
+        expected: String,
+        found: String,
+    },
+
+    #[error("{0} does not start with a header line")]
+    MissingHeader(String),
+
+    #[error("Line {line} of {path} is not a channel result; only the last line may be incomplete")]
+    CorruptLine { path: String, line: usize },
+
+    #[error(
+        "The result for {freq} Hz in {path} has no Fisher matrix, which the spectral basis and power spectrum need"
+    )]
+    MissingFisher { path: String, freq: f64 },
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
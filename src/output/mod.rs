@@ -0,0 +1,281 @@
+//! Per-channel results, written as JSON lines as soon as each channel is done
+//! so that a long run can be resumed. The first line is a [`Header`] recording
+//! the hash of the config; every other line is a [`ChannelResult`].
+
+mod error;
+
+pub use error::*;
+
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use ndarray::prelude::*;
+use num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+
+use crate::channels::Channel;
+
+/// Results for channels without a coarse/fine channel number are matched by
+/// frequency to within this [Hz], since the JSON round trip need not be exact.
+const FREQ_TOLERANCE: f64 = 1e-3;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Header {
+    /// Hash of the config the results were calculated with
+    pub config_hash: String,
+}
+
+/// The CRB of a single channel.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChannelResult {
+    /// Centre frequency [Hz]
+    pub freq: f64,
+
+    pub coarse_chan: Option<usize>,
+    pub fine_chan: Option<usize>,
+
+    /// Number of selected cross-correlation baselines
+    pub num_baselines: usize,
+
+    /// Noise on each visibility [Jy]
+    pub sigma: f64,
+
+    /// Number of sky-model components used
+    pub num_components: usize,
+
+    /// CRB of each antenna's log-amplitude gain
+    pub crb: Vec<f64>,
+
+    /// The (real) Fisher matrix, row by row; only kept when it is needed
+    /// across channels, for a spectral basis or power spectrum
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fisher: Option<Vec<f64>>,
+}
+
+impl ChannelResult {
+    /// Whether this is the result for `channel`.
+    pub fn is_for(&self, channel: &Channel) -> bool {
+        if self.coarse_chan != channel.coarse_chan || self.fine_chan != channel.fine_chan {
+            return false;
+        }
+        return match (channel.coarse_chan, channel.fine_chan) {
+            (Some(_), Some(_)) => true,
+            _ => (self.freq - channel.freq).abs() <= FREQ_TOLERANCE,
+        };
+    }
+
+    /// The Fisher matrix, if it was kept.
+    pub fn fisher(&self) -> Option<Array2<Complex64>> {
+        let fisher = self.fisher.as_ref()?;
+        let n = self.crb.len();
+        return Some(Array2::from_shape_fn((n, n), |(a, b)| {
+            Complex64::new(fisher[a * n + b], 0.0)
+        }));
+    }
+}
+
+/// Appends each channel's result to the output file and flushes it.
+pub struct ResultWriter {
+    path: PathBuf,
+    writer: BufWriter<fs::File>,
+}
+
+impl ResultWriter {
+    /// Start a new output file, or with `resume`, carry on with an existing
+    /// one (if there is one), returning the results already in it. Resuming
+    /// fails if those results were calculated with a different config.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        config_hash: &str,
+        resume: bool,
+    ) -> Result<(Self, Vec<ChannelResult>), OutputError> {
+        let path = path.as_ref().to_path_buf();
+        let display = path.display().to_string();
+
+        if resume && path.exists() {
+            let mut reader = BufReader::new(fs::File::open(&path)?);
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            if !line.ends_with('\n') {
+                return Err(OutputError::MissingHeader(display));
+            }
+            let header: Header = serde_json::from_str(&line)?;
+            if header.config_hash != config_hash {
+                return Err(OutputError::ConfigMismatch {
+                    path: display,
+                    expected: config_hash.to_string(),
+                    found: header.config_hash,
+                });
+            }
+
+            // A crash can leave a partly-written last line. Anything that
+            // doesn't parse before that means the file is damaged.
+            let mut completed = vec![];
+            let mut good_len = line.len() as u64;
+            let mut line_number = 1;
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 {
+                    break;
+                }
+                line_number += 1;
+                let result = if line.ends_with('\n') {
+                    serde_json::from_str::<ChannelResult>(&line).ok()
+                } else {
+                    None
+                };
+                match result {
+                    Some(result) => {
+                        completed.push(result);
+                        good_len += line.len() as u64;
+                    }
+                    None if reader.fill_buf()?.is_empty() => break,
+                    None => {
+                        return Err(OutputError::CorruptLine {
+                            path: display,
+                            line: line_number,
+                        })
+                    }
+                }
+            }
+
+            // Drop only the partial line, keeping everything before it, and
+            // carry on after the last complete result.
+            let file = OpenOptions::new().append(true).open(&path)?;
+            file.set_len(good_len)?;
+            let writer = Self {
+                path,
+                writer: BufWriter::new(file),
+            };
+            return Ok((writer, completed));
+        }
+
+        let header = Header {
+            config_hash: config_hash.to_string(),
+        };
+        return Ok((Self::create(&path, &header)?, vec![]));
+    }
+
+    fn create(path: &Path, header: &Header) -> Result<Self, OutputError> {
+        let mut writer = Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(fs::File::create(path)?),
+        };
+        writer.write_line(header)?;
+        writer.writer.flush()?;
+        return Ok(writer);
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> Result<(), OutputError> {
+        serde_json::to_writer(&mut self.writer, value)?;
+        writeln!(self.writer)?;
+        return Ok(());
+    }
+
+    /// Write a channel's result, making sure it reaches the file.
+    pub fn write(&mut self, result: &ChannelResult) -> Result<(), OutputError> {
+        self.write_line(result)?;
+        self.writer.flush()?;
+        return Ok(());
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(freq: f64) -> ChannelResult {
+        return ChannelResult {
+            freq,
+            coarse_chan: None,
+            fine_chan: None,
+            num_baselines: 3,
+            sigma: 0.1,
+            num_components: 2,
+            crb: vec![1e-4, 2e-4, 3e-4],
+            fisher: None,
+        };
+    }
+
+    fn channel(freq: f64) -> Channel {
+        return Channel {
+            freq,
+            coarse_chan: None,
+            fine_chan: None,
+        };
+    }
+
+    /// A fresh output path in the temporary directory.
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("crb_{}_{name}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        return path;
+    }
+
+    #[test]
+    fn resume_drops_only_a_partial_last_line() {
+        let path = temp_path("partial");
+        let (mut writer, _) = ResultWriter::open(&path, "hash", false).unwrap();
+        writer.write(&result(150e6)).unwrap();
+        writer.write(&result(151e6)).unwrap();
+        drop(writer);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"freq\": 152000000.0, \"coarse").unwrap();
+        drop(file);
+
+        let (mut writer, completed) = ResultWriter::open(&path, "hash", true).unwrap();
+        assert_eq!(completed.len(), 2);
+        assert!(completed[1].is_for(&channel(151e6)));
+        writer.write(&result(152e6)).unwrap();
+        drop(writer);
+
+        let (_, completed) = ResultWriter::open(&path, "hash", true).unwrap();
+        let freqs: Vec<f64> = completed.iter().map(|r| r.freq).collect();
+        assert_eq!(freqs, vec![150e6, 151e6, 152e6]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn resume_rejects_a_corrupt_line_before_the_end() {
+        let path = temp_path("corrupt");
+        let (mut writer, _) = ResultWriter::open(&path, "hash", false).unwrap();
+        writer.write(&result(150e6)).unwrap();
+        writer.writer.write_all(b"not json\n").unwrap();
+        writer.write(&result(151e6)).unwrap();
+        drop(writer);
+        let before = fs::read(&path).unwrap();
+
+        assert!(matches!(
+            ResultWriter::open(&path, "hash", true),
+            Err(OutputError::CorruptLine { line: 3, .. })
+        ));
+        assert!(matches!(
+            ResultWriter::open(&path, "other", true),
+            Err(OutputError::ConfigMismatch { .. })
+        ));
+        // Failing to resume leaves the file alone.
+        assert_eq!(fs::read(&path).unwrap(), before);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn results_match_channels_after_a_round_trip() {
+        let freq = 167.035e6 + 1.0 / 3.0;
+        let json = serde_json::to_string(&result(freq)).unwrap();
+        let result: ChannelResult = serde_json::from_str(&json).unwrap();
+        assert!(result.is_for(&channel(freq)));
+        assert!(!result.is_for(&channel(freq + 1e3)));
+
+        let fine = Channel {
+            freq,
+            coarse_chan: Some(133),
+            fine_chan: Some(4),
+        };
+        assert!(!result.is_for(&fine));
+    }
+}
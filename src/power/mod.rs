@@ -0,0 +1,438 @@
+//! Propagate the gain CRB into the delay power spectrum. Errors in the
+//! calibrated gains multiply the foreground visibilities, so any spectral
+//! structure in the errors leaks foreground power out of the "wedge" and into
+//! the delays (`k_par`) used for EoR measurements.
+//!
+//! Power is in visibility units, [Jy^2 Hz^2]; delays are converted to
+//! cosmological wavenumbers for the 21 cm line in a flat LCDM cosmology.
+
+use std::f64::consts::TAU;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+use marlu::RADec;
+use ndarray::prelude::*;
+use num_complex::*;
+use rayon::prelude::*;
+
+use crate::calc::{self, Averaging};
+use crate::srclist::*;
+
+/// Rest frequency of the 21 cm line [Hz]
+const F21: f64 = 1420.405751e6;
+
+/// Matter density parameter (Planck 2015)
+const OMEGA_M: f64 = 0.3089;
+
+/// Speed of light [km/s]
+const C_KMS: f64 = 299792.458;
+
+/// Number of steps in the comoving-distance integral.
+const NUM_DISTANCE_STEPS: usize = 1000;
+
+/// The covariance of every antenna's log-amplitude gain errors across
+/// frequency.
+pub enum GainCovariance<'a> {
+    /// Errors independent between channels, with the CRB of each channel
+    /// (the covariance between antennas)
+    Independent(&'a [Array2<Complex64>]),
+
+    /// Errors from a smooth spectral fit, `C_ab = Phi C_coef,ab Phi^T`, with
+    /// the basis evaluated at every channel (rows) and the coefficient CRB
+    /// indexed by `antenna * num_basis + k`
+    Spectral {
+        basis: &'a Array2<f64>,
+        coefficients: &'a Array2<Complex64>,
+    },
+}
+
+pub struct DelaySpectrum {
+    /// Non-negative delays [s]
+    pub delays: Array1<f64>,
+
+    /// Line-of-sight wavenumber of each delay [h Mpc^-1]
+    pub k_par: Array1<f64>,
+
+    /// Expected contamination at each delay, averaged over baselines
+    /// [Jy^2 Hz^2]
+    pub power_1d: Array1<f64>,
+
+    /// Centre of each perpendicular wavenumber bin [h Mpc^-1]
+    pub k_perp: Array1<f64>,
+
+    /// Expected contamination in each `k_perp` bin (rows) and at each delay
+    /// (columns), averaged over baselines; NaN for empty bins [Jy^2 Hz^2]
+    pub power_2d: Array2<f64>,
+}
+
+/// The foreground visibility of every baseline `a < b` (columns, ordered as
+/// `pairs`) in every channel (rows).
+pub fn foreground_visibilities(
+    baselines_xy: &Array3<f64>,
+    foregrounds: &[&Component],
+    pairs: &[(usize, usize)],
+    freqs: &[f64],
+    phase_centre: RADec,
+    averaging: Averaging,
+) -> Array2<Complex64> {
+    let mut vis = Array2::<Complex64>::zeros((freqs.len(), pairs.len()));
+    for (mut row, &freq) in vis.axis_iter_mut(Axis(0)).zip(freqs) {
+        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / freq;
+        let model = calc::model_visibilities(
+            baselines_xy,
+            foregrounds,
+            lambda,
+            freq,
+            phase_centre,
+            averaging,
+        );
+        for (v, &(a, b)) in row.iter_mut().zip(pairs) {
+            *v = model[[a, b]];
+        }
+    }
+    return vis;
+}
+
+/// The expected power of the gain-error contamination, `dV_ab(f) = (e_a(f) +
+/// e_b(f)) V_ab(f)`, after a Blackman-Harris-windowed delay transform. For each
+/// baseline, `P(tau) = y(tau)^H (C_aa + C_bb + C_ab + C_ba) y(tau)` where `y`
+/// is the delay transform of the windowed foregrounds projected onto the
+/// error covariance.
+pub fn delay_spectrum(
+    baselines_xy: &Array3<f64>,
+    pairs: &[(usize, usize)],
+    foregrounds: &Array2<Complex64>,
+    freqs: &[f64],
+    channel_width: f64,
+    covariance: &GainCovariance,
+    num_kperp_bins: usize,
+) -> DelaySpectrum {
+    let min = freqs.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = freqs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let centre = (min + max) / 2.0;
+
+    // Delays of a regular grid of channels across the band, including any
+    // that are flagged or missing.
+    let num_grid = ((max - min) / channel_width).round() as usize + 1;
+    let num_delays = num_grid / 2 + 1;
+    let delays =
+        Array1::from_shape_fn(num_delays, |k| k as f64 / (num_grid as f64 * channel_width));
+
+    let window: Vec<f64> = freqs
+        .iter()
+        .map(|f| {
+            blackman_harris(if max > min {
+                (f - min) / (max - min)
+            } else {
+                0.5
+            })
+        })
+        .collect();
+
+    // Transform at +tau and -tau; the power is folded afterwards.
+    let transform = Array2::from_shape_fn((2 * num_delays, freqs.len()), |(t, f)| {
+        let tau = if t < num_delays {
+            delays[t]
+        } else {
+            -delays[t - num_delays]
+        };
+        Complex64::from_polar(channel_width, -TAU * freqs[f] * tau)
+    });
+
+    let power: Vec<Array1<f64>> = pairs
+        .par_iter()
+        .enumerate()
+        .map(|(i, &(a, b))| {
+            let weighted: Array1<Complex64> = foregrounds
+                .column(i)
+                .iter()
+                .zip(window.iter())
+                .map(|(v, w)| v * *w)
+                .collect();
+
+            let both = match covariance {
+                GainCovariance::Independent(crbs) => {
+                    // Errors uncorrelated between channels spread power evenly
+                    // over delay.
+                    let p: f64 = weighted
+                        .iter()
+                        .zip(crbs.iter())
+                        .map(|(v, c)| {
+                            v.norm_sqr() * (c[[a, a]] + c[[b, b]] + c[[a, b]] + c[[b, a]]).re
+                        })
+                        .sum::<f64>()
+                        * channel_width.powi(2);
+                    Array1::from_elem(2 * num_delays, p)
+                }
+
+                GainCovariance::Spectral {
+                    basis,
+                    coefficients,
+                } => {
+                    let num_basis = basis.ncols();
+                    let projected = Array2::from_shape_fn((freqs.len(), num_basis), |(f, k)| {
+                        weighted[f] * basis[[f, k]]
+                    });
+                    let y = transform.dot(&projected);
+                    let block = |p: usize, q: usize| {
+                        coefficients.slice(s![
+                            p * num_basis..(p + 1) * num_basis,
+                            q * num_basis..(q + 1) * num_basis
+                        ])
+                    };
+                    let c = &block(a, a) + &block(b, b) + block(a, b) + block(b, a);
+                    y.axis_iter(Axis(0))
+                        .map(|y_t| {
+                            let cy = c.dot(&y_t);
+                            y_t.iter()
+                                .zip(cy.iter())
+                                .map(|(y, cy)| y.conj() * cy)
+                                .sum::<Complex64>()
+                                .re
+                        })
+                        .collect()
+                }
+            };
+
+            let folded: Array1<f64> = (0..num_delays)
+                .map(|t| (both[t] + both[num_delays + t]) / 2.0)
+                .collect();
+            return folded;
+        })
+        .collect();
+
+    let (k_par_scale, distance) = cosmology(centre);
+    let k_par = delays.mapv(|tau| TAU * tau * k_par_scale);
+
+    let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / centre;
+    let k_perps: Vec<f64> = pairs
+        .iter()
+        .map(|&(a, b)| {
+            let length = baselines_xy[[a, b, 0]].hypot(baselines_xy[[a, b, 1]]);
+            TAU * length / lambda / distance
+        })
+        .collect();
+    let k_perp_min = k_perps.iter().copied().fold(f64::INFINITY, f64::min);
+    let k_perp_max = k_perps.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let bin_width = (k_perp_max - k_perp_min) / num_kperp_bins as f64;
+    let k_perp = Array1::from_shape_fn(num_kperp_bins, |i| {
+        k_perp_min + (i as f64 + 0.5) * bin_width
+    });
+
+    let mut power_1d = Array1::<f64>::zeros(num_delays);
+    let mut power_2d = Array2::<f64>::zeros((num_kperp_bins, num_delays));
+    let mut counts = vec![0usize; num_kperp_bins];
+    for (p, k) in power.iter().zip(k_perps.iter()) {
+        power_1d += p;
+        let bin = if bin_width > 0.0 {
+            (((k - k_perp_min) / bin_width) as usize).min(num_kperp_bins - 1)
+        } else {
+            0
+        };
+        let mut row = power_2d.row_mut(bin);
+        row += p;
+        counts[bin] += 1;
+    }
+    power_1d /= pairs.len() as f64;
+    for (mut row, &count) in power_2d.axis_iter_mut(Axis(0)).zip(counts.iter()) {
+        row.mapv_inplace(|p| {
+            if count > 0 {
+                p / count as f64
+            } else {
+                f64::NAN
+            }
+        });
+    }
+
+    return DelaySpectrum {
+        delays,
+        k_par,
+        power_1d,
+        k_perp,
+        power_2d,
+    };
+}
+
+/// Write `{prefix}_1d.txt` (delay, k_par, power) and `{prefix}_2d.txt` (one row
+/// per k_perp bin, one column per k_par).
+pub fn write_delay_spectrum(spectrum: &DelaySpectrum, prefix: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(fs::File::create(format!("{prefix}_1d.txt"))?);
+    writeln!(out, "# delay [s]  k_par [h/Mpc]  power [Jy^2 Hz^2]")?;
+    for ((tau, k), p) in spectrum
+        .delays
+        .iter()
+        .zip(spectrum.k_par.iter())
+        .zip(spectrum.power_1d.iter())
+    {
+        writeln!(out, "{:e} {:e} {:e}", tau, k, p)?;
+    }
+
+    let mut out = BufWriter::new(fs::File::create(format!("{prefix}_2d.txt"))?);
+    writeln!(
+        out,
+        "# rows: k_perp [h/Mpc] (first column), columns: k_par [h/Mpc]"
+    )?;
+    let k_par: Vec<String> = spectrum.k_par.iter().map(|k| format!("{:e}", k)).collect();
+    writeln!(out, "# {}", k_par.join(" "))?;
+    for (k, row) in spectrum
+        .k_perp
+        .iter()
+        .zip(spectrum.power_2d.axis_iter(Axis(0)))
+    {
+        let row: Vec<String> = row.iter().map(|p| format!("{:e}", p)).collect();
+        writeln!(out, "{:e} {}", k, row.join(" "))?;
+    }
+
+    return Ok(());
+}
+
+/// Four-term Blackman-Harris window at `x` in [0, 1].
+fn blackman_harris(x: f64) -> f64 {
+    let t = TAU * x;
+    return 0.35875 - 0.48829 * t.cos() + 0.14128 * (2.0 * t).cos() - 0.01168 * (3.0 * t).cos();
+}
+
+/// For 21 cm emission observed at `freq`, the factor converting delay to
+/// `k_par / 2 pi` [h Mpc^-1 s^-1] and the transverse comoving distance
+/// [Mpc h^-1], with `H0 = 100 h km/s/Mpc`.
+fn cosmology(freq: f64) -> (f64, f64) {
+    let z = F21 / freq - 1.0;
+    let e = |z: f64| (OMEGA_M * (1.0 + z).powi(3) + 1.0 - OMEGA_M).sqrt();
+
+    // Simpson's rule for the comoving distance integral.
+    let h = z / NUM_DISTANCE_STEPS as f64;
+    let mut integral = 1.0 / e(0.0) + 1.0 / e(z);
+    for i in 1..NUM_DISTANCE_STEPS {
+        let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+        integral += weight / e(i as f64 * h);
+    }
+    let distance = C_KMS / 100.0 * integral * h / 3.0;
+
+    let k_par_scale = F21 * 100.0 * e(z) / (C_KMS * (1.0 + z).powi(2));
+    return (k_par_scale, distance);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NUM_ANTS: usize = 3;
+    const NUM_CHANS: usize = 6;
+    const CHANNEL_WIDTH: f64 = 1e6;
+
+    /// A symmetric positive-semidefinite matrix `A A^T`.
+    fn covariance(n: usize, seed: f64) -> Array2<f64> {
+        let a = Array2::from_shape_fn((n, n), |(i, j)| {
+            (seed + 7.0 * i as f64 + 3.0 * j as f64).sin()
+        });
+        return a.dot(&a.t());
+    }
+
+    /// Sum over delays of the folded power of baseline `(0, 1)`, found
+    /// directly from the covariance `cov(f, f')` of `e_0(f) + e_1(f)`.
+    fn brute_force(
+        freqs: &[f64],
+        foregrounds: &Array2<Complex64>,
+        delays: &Array1<f64>,
+        cov: impl Fn(usize, usize) -> f64,
+    ) -> Vec<f64> {
+        let (min, max) = (freqs[0], freqs[NUM_CHANS - 1]);
+        let power = |tau: f64| {
+            let y: Vec<Complex64> = (0..NUM_CHANS)
+                .map(|f| {
+                    let window = blackman_harris((freqs[f] - min) / (max - min));
+                    Complex64::from_polar(CHANNEL_WIDTH * window, -TAU * freqs[f] * tau)
+                        * foregrounds[[f, 0]]
+                })
+                .collect();
+            let mut p = Complex64::new(0.0, 0.0);
+            for f in 0..NUM_CHANS {
+                for g in 0..NUM_CHANS {
+                    p += y[f].conj() * y[g] * cov(f, g);
+                }
+            }
+            p.re
+        };
+        return delays
+            .iter()
+            .map(|&tau| (power(tau) + power(-tau)) / 2.0)
+            .collect();
+    }
+
+    fn assert_matches(spectrum: &DelaySpectrum, expected: &[f64]) {
+        for (p, e) in spectrum.power_1d.iter().zip(expected) {
+            assert!((p - e).abs() <= 1e-9 * e.abs(), "{p} != {e}");
+        }
+    }
+
+    #[test]
+    fn propagates_cross_antenna_covariance() {
+        let freqs: Vec<f64> = (0..NUM_CHANS)
+            .map(|f| 150e6 + f as f64 * CHANNEL_WIDTH)
+            .collect();
+        let foregrounds = Array2::from_shape_fn((NUM_CHANS, 1), |(f, _)| {
+            Complex64::from_polar(1.0 + 0.1 * f as f64, 0.7 * f as f64)
+        });
+        let baselines_xy = Array3::from_shape_fn((NUM_ANTS, NUM_ANTS, 2), |(a, b, k)| {
+            (a as f64 - b as f64) * (10.0 + k as f64)
+        });
+        let pairs = [(0, 1)];
+
+        // Independent channels, each with its own covariance between antennas
+        let crbs: Vec<Array2<Complex64>> = (0..NUM_CHANS)
+            .map(|f| covariance(NUM_ANTS, f as f64).mapv(|c| Complex64::new(c, 0.0)))
+            .collect();
+        let spectrum = delay_spectrum(
+            &baselines_xy,
+            &pairs,
+            &foregrounds,
+            &freqs,
+            CHANNEL_WIDTH,
+            &GainCovariance::Independent(&crbs),
+            1,
+        );
+        let expected = brute_force(&freqs, &foregrounds, &spectrum.delays, |f, g| {
+            if f != g {
+                return 0.0;
+            }
+            let c = &crbs[f];
+            (c[[0, 0]] + c[[1, 1]] + c[[0, 1]] + c[[1, 0]]).re
+        });
+        assert_matches(&spectrum, &expected);
+
+        // A smooth fit with a quadratic basis
+        let num_basis = 3;
+        let basis = Array2::from_shape_fn((NUM_CHANS, num_basis), |(f, k)| {
+            (f as f64 / (NUM_CHANS - 1) as f64).powi(k as i32)
+        });
+        let coefficients = covariance(NUM_ANTS * num_basis, 1.0);
+        let spectrum = delay_spectrum(
+            &baselines_xy,
+            &pairs,
+            &foregrounds,
+            &freqs,
+            CHANNEL_WIDTH,
+            &GainCovariance::Spectral {
+                basis: &basis,
+                coefficients: &coefficients.mapv(|c| Complex64::new(c, 0.0)),
+            },
+            1,
+        );
+        let expected = brute_force(&freqs, &foregrounds, &spectrum.delays, |f, g| {
+            let mut cov = 0.0;
+            for p in [0, 1] {
+                for q in [0, 1] {
+                    for k in 0..num_basis {
+                        for l in 0..num_basis {
+                            cov += basis[[f, k]]
+                                * coefficients[[p * num_basis + k, q * num_basis + l]]
+                                * basis[[g, l]];
+                        }
+                    }
+                }
+            }
+            cov
+        });
+        assert_matches(&spectrum, &expected);
+    }
+}
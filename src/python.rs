@@ -0,0 +1,243 @@
+//! Python bindings, enabled with the `python` feature and built with maturin.
+//! Arrays are passed to and from Python as numpy arrays; angles are in
+//! degrees and frequencies in Hz, as in the config.
+//!
+//! ```python
+//! import crb
+//! comps = crb.ComponentList.from_file("srclist.yaml")
+//! comps.veto_by_fov(ra, dec, freq, diameter)
+//! baselines = crb.baselines("obs.metafits")
+//! fisher = crb.fisher_matrix(baselines, comps, freq, sigma, ra, dec)
+//! bound, null_space = crb.calculate_crb(fisher)
+//! ```
+
+use marlu::RADec;
+use mwalib::MetafitsContext;
+use ndarray::prelude::*;
+use num_complex::Complex64;
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3, PyReadonlyArray2, PyReadonlyArray3};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::calc::{self, Averaging};
+use crate::config::{Gauge, LengthUnits, Telescope, UvRange};
+use crate::srclist::{read, Component, ComponentList, Veto};
+use crate::CrbError;
+
+/// A complex numpy array owned by Python.
+type ComplexArray<'py> = Bound<'py, PyArray2<Complex64>>;
+
+impl From<CrbError> for PyErr {
+    fn from(e: CrbError) -> Self {
+        return match e {
+            CrbError::IO(e) => PyIOError::new_err(e.to_string()),
+            e => PyValueError::new_err(e.to_string()),
+        };
+    }
+}
+
+/// The components of a sky model, flattened out of their sources.
+#[pyclass(name = "ComponentList")]
+struct PyComponentList(ComponentList);
+
+#[pymethods]
+impl PyComponentList {
+    /// Read a yaml or json source list, one source at a time. Hour angles
+    /// need `lst` [degrees]. With `min_flux` [Jy], fainter components are
+    /// dropped as they are read.
+    #[staticmethod]
+    #[pyo3(signature = (path, lst=None, min_flux=None))]
+    fn from_file(path: &str, lst: Option<f64>, min_flux: Option<f64>) -> PyResult<Self> {
+        let veto = Veto {
+            min_flux,
+            fov: None,
+        };
+        let components =
+            read::component_list_from_file(path, lst, &veto).map_err(CrbError::from)?;
+        return Ok(Self(components));
+    }
+
+    fn __len__(&self) -> usize {
+        return self.0.len();
+    }
+
+    /// Drop components whose reference Stokes I is at most `noise` [Jy].
+    fn veto_by_flux(&mut self, noise: f64) {
+        self.0.veto_by_flux(noise);
+    }
+
+    /// Drop components outside the field of view of a station of `diameter`
+    /// [m] at `freq`, centred on (`ra`, `dec`).
+    fn veto_by_fov(&mut self, ra: f64, dec: f64, freq: f64, diameter: f64) {
+        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / freq;
+        self.0
+            .veto_by_fov(RADec::from_degrees(ra, dec), lambda, diameter);
+    }
+
+    /// Keep only the `num` components brightest at `freq`.
+    fn brightest(&mut self, num: usize, freq: f64) {
+        let components: Vec<&Component> = self.0.iter().collect();
+        let indices = calc::sky::brightest(&components, num, freq);
+        self.0 = self.0.select(&indices);
+    }
+
+    /// RA and Dec of every component [degrees], shape `(n, 2)`.
+    fn positions<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray2<f64>> {
+        let positions = Array2::from_shape_fn((self.0.len(), 2), |(c, k)| {
+            let radec = self.0.as_slice()[c].radec;
+            let angle = if k == 0 { radec.ra } else { radec.dec };
+            angle.to_degrees()
+        });
+        return positions.into_pyarray(py);
+    }
+
+    /// Stokes I of every component at `freq` [Jy].
+    fn fluxes<'py>(&self, py: Python<'py>, freq: f64) -> Bound<'py, PyArray1<f64>> {
+        let fluxes: Array1<f64> = self
+            .0
+            .iter()
+            .map(|comp| comp.estimate_at_freq(freq).i)
+            .collect();
+        return fluxes.into_pyarray(py);
+    }
+}
+
+/// The (east, north) separation of every pair of antennas in a metafits [m],
+/// shape `(n, n, 2)`.
+#[pyfunction]
+fn baselines<'py>(py: Python<'py>, metafits: &str) -> PyResult<Bound<'py, PyArray3<f64>>> {
+    let metafits = MetafitsContext::new(metafits, None).map_err(CrbError::from)?;
+    return Ok(calc::create_baselines(&metafits).into_pyarray(py));
+}
+
+/// The noise on the real part of a visibility [Jy].
+#[pyfunction]
+#[pyo3(signature = (t_sys, bandwidth, int_time, telescope="mwa"))]
+fn visibility_noise(t_sys: f64, bandwidth: f64, int_time: f64, telescope: &str) -> PyResult<f64> {
+    let telescope = match telescope {
+        "mwa" => Telescope::Mwa,
+        "ska" => Telescope::Ska,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "unknown telescope '{telescope}', expected 'mwa' or 'ska'"
+            )))
+        }
+    };
+    return Ok(calc::calc_rms(t_sys, bandwidth, int_time, telescope));
+}
+
+/// The Fisher information matrix of every antenna's log-amplitude gain at
+/// `freq`, with visibility noise `sigma` [Jy] and the phase centre at
+/// (`ra`, `dec`). Only baselines between `uv_min` and `uv_max` [m] are used.
+#[pyfunction]
+#[pyo3(signature = (
+    baselines, components, freq, sigma, ra, dec,
+    channel_width=0.0, int_time=0.0, uv_min=None, uv_max=None, autocorrelation_noise=None
+))]
+#[allow(clippy::too_many_arguments)]
+fn fisher_matrix<'py>(
+    py: Python<'py>,
+    baselines: PyReadonlyArray3<f64>,
+    components: &PyComponentList,
+    freq: f64,
+    sigma: f64,
+    ra: f64,
+    dec: f64,
+    channel_width: f64,
+    int_time: f64,
+    uv_min: Option<f64>,
+    uv_max: Option<f64>,
+    autocorrelation_noise: Option<f64>,
+) -> PyResult<Bound<'py, PyArray2<Complex64>>> {
+    let baselines = baselines.as_array().to_owned();
+    if !matches!(baselines.shape(), &[n, m, 2] if n == m) {
+        return Err(shape_error("baselines", "(n, n, 2)", baselines.shape()));
+    }
+    let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / freq;
+    let uv_range = UvRange {
+        min: uv_min,
+        max: uv_max,
+        units: LengthUnits::Metres,
+    };
+    let selection = uv_range.selection(&baselines, lambda);
+
+    let components: Vec<&Component> = components.0.iter().collect();
+
+    // Release the GIL for the calculation itself.
+    let fisher = py.allow_threads(|| {
+        calc::fisher_matrix(
+            &baselines,
+            &components,
+            lambda,
+            freq,
+            sigma,
+            RADec::from_degrees(ra, dec),
+            Averaging {
+                channel_width,
+                int_time,
+            },
+            &selection,
+            autocorrelation_noise,
+        )
+    });
+    return Ok(fisher.into_pyarray(py));
+}
+
+/// Invert a Fisher matrix into the CRB, returning the bound and the null
+/// space it discarded. `gauge` is one of "inverse", "reference_antenna"
+/// (with `reference_antenna`), "project" or "pseudoinverse"; with "project",
+/// `degenerate` holds the known degenerate directions as columns.
+#[pyfunction]
+#[pyo3(signature = (fisher, gauge="inverse", reference_antenna=None, degenerate=None))]
+fn calculate_crb<'py>(
+    py: Python<'py>,
+    fisher: PyReadonlyArray2<Complex64>,
+    gauge: &str,
+    reference_antenna: Option<usize>,
+    degenerate: Option<PyReadonlyArray2<Complex64>>,
+) -> PyResult<(ComplexArray<'py>, ComplexArray<'py>)> {
+    let gauge = match (gauge, reference_antenna) {
+        ("inverse", _) => Gauge::Inverse,
+        ("reference_antenna", Some(reference)) => Gauge::ReferenceAntenna(reference),
+        ("project", _) => Gauge::Project,
+        ("pseudoinverse", _) => Gauge::Pseudoinverse,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+            "unknown gauge '{gauge}' (a reference antenna must be given with 'reference_antenna')"
+        )))
+        }
+    };
+    let fisher = fisher.as_array().to_owned();
+    if !fisher.is_square() {
+        return Err(shape_error("fisher", "(n, n)", fisher.shape()));
+    }
+    let degenerate = match degenerate {
+        Some(degenerate) => degenerate.as_array().to_owned(),
+        None => Array2::zeros((fisher.nrows(), 0)),
+    };
+    if degenerate.nrows() != fisher.nrows() {
+        return Err(shape_error("degenerate", "(n, k)", degenerate.shape()));
+    }
+
+    let crb = py
+        .allow_threads(|| calc::calculate_crb(&fisher, &gauge, &degenerate))
+        .map_err(CrbError::from)?;
+    return Ok((crb.matrix.into_pyarray(py), crb.null_space.into_pyarray(py)));
+}
+
+/// The error for an array argument of the wrong shape.
+fn shape_error(name: &str, expected: &str, shape: &[usize]) -> PyErr {
+    return PyValueError::new_err(format!(
+        "'{name}' must have shape {expected}, not {shape:?}"
+    ));
+}
+
+#[pymodule]
+fn crb(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyComponentList>()?;
+    m.add_function(wrap_pyfunction!(baselines, m)?)?;
+    m.add_function(wrap_pyfunction!(visibility_noise, m)?)?;
+    m.add_function(wrap_pyfunction!(fisher_matrix, m)?)?;
+    m.add_function(wrap_pyfunction!(calculate_crb, m)?)?;
+    return Ok(());
+}
@@ -0,0 +1,119 @@
+//! Human-readable summaries of inputs and results.
+
+use std::io::{self, Write};
+
+use log::info;
+use mwalib::MetafitsContext;
+use ndarray::prelude::*;
+use num_complex::Complex64;
+
+use crate::srclist::*;
+
+/// Log how many directions the gauge left unconstrained, and how close each
+/// is to a change common to every parameter (e.g. the overall phase).
+pub fn report_null_space(null_space: &Array2<Complex64>) {
+    if null_space.ncols() == 0 {
+        return;
+    }
+    info!("Discarded {} null-space directions", null_space.ncols());
+    let norm = (null_space.nrows() as f64).sqrt();
+    for (k, direction) in null_space.axis_iter(Axis(1)).enumerate() {
+        let overlap = direction.sum().norm() / norm;
+        info!(
+            "  direction {}: overlap with a common change {:.6}",
+            k, overlap
+        );
+    }
+}
+
+/// Write the number of sources and components in a source list, the types of
+/// its components, and the range of their reference flux densities.
+pub fn summarise_source_list<W: Write>(out: &mut W, source_list: &SourceList) -> io::Result<()> {
+    let components: Vec<&SourceComponent> = source_list
+        .values()
+        .flat_map(|source| source.components.iter())
+        .collect();
+    let num_points = components.iter().filter(|c| c.is_point()).count();
+    let num_gaussians = components.iter().filter(|c| c.is_gaussian()).count();
+    let num_shapelets = components.iter().filter(|c| c.is_shapelet()).count();
+    let (mut num_lists, mut num_power_laws, mut num_curved) = (0, 0, 0);
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    for comp in components.iter() {
+        let fd = match &comp.flux_type {
+            FluxDensityType::List(fds) => {
+                num_lists += 1;
+                fds.first()
+            }
+            FluxDensityType::PowerLaw { fd, .. } => {
+                num_power_laws += 1;
+                fd
+            }
+            FluxDensityType::CurvedPowerLaw { fd, .. } => {
+                num_curved += 1;
+                fd
+            }
+        };
+        min = min.min(fd.i);
+        max = max.max(fd.i);
+    }
+
+    writeln!(out, "Sources: {}", source_list.len())?;
+    writeln!(
+        out,
+        "Components: {} ({} point, {} gaussian, {} shapelet)",
+        components.len(),
+        num_points,
+        num_gaussians,
+        num_shapelets
+    )?;
+    writeln!(
+        out,
+        "Flux density types: {} list, {} power law, {} curved power law",
+        num_lists, num_power_laws, num_curved
+    )?;
+    writeln!(out, "Reference Stokes I: {} to {} Jy", min, max)?;
+    return Ok(());
+}
+
+/// Write the parts of a metafits file that matter for the CRB.
+pub fn summarise_metafits<W: Write>(out: &mut W, metafits: &MetafitsContext) -> io::Result<()> {
+    writeln!(
+        out,
+        "Observation: {} ({})",
+        metafits.obs_id, metafits.obs_name
+    )?;
+    writeln!(out, "Start: {}", metafits.sched_start_utc)?;
+    writeln!(
+        out,
+        "Duration: {} s",
+        metafits.sched_duration_ms as f64 / 1e3
+    )?;
+    match (
+        metafits.ra_phase_center_degrees,
+        metafits.dec_phase_center_degrees,
+    ) {
+        (Some(ra), Some(dec)) => writeln!(out, "Phase centre: RA {} deg, Dec {} deg", ra, dec)?,
+        _ => writeln!(out, "Phase centre: none")?,
+    }
+    writeln!(
+        out,
+        "Pointing: RA {} deg, Dec {} deg",
+        metafits.ra_tile_pointing_degrees, metafits.dec_tile_pointing_degrees
+    )?;
+    writeln!(out, "LST: {} deg", metafits.lst_deg)?;
+    writeln!(out, "Antennas: {}", metafits.num_ants)?;
+    writeln!(
+        out,
+        "Centre frequency: {} MHz, bandwidth {} MHz over {} coarse channels",
+        metafits.centre_freq_hz as f64 / 1e6,
+        metafits.obs_bandwidth_hz as f64 / 1e6,
+        metafits.metafits_coarse_chans.len()
+    )?;
+    writeln!(
+        out,
+        "Fine channel width: {} kHz, integration time: {} s",
+        metafits.corr_fine_chan_width_hz as f64 / 1e3,
+        metafits.corr_int_time_ms as f64 / 1e3
+    )?;
+    return Ok(());
+}
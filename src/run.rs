@@ -0,0 +1,530 @@
+//! The `crb` command: the CRB of the gains in every channel, and any of the
+//! optional extras enabled in the config.
+
+use std::time::Instant;
+
+use log::{debug, info, warn};
+use mwalib::MetafitsContext;
+use ndarray::prelude::*;
+use num_complex::Complex64;
+
+use crate::config::{self, Config, Gauge, Observation};
+use crate::output::{ChannelResult, OutputError, ResultWriter};
+use crate::srclist::{
+    cluster_components, generate, read, singletons, write, Component, ComponentList, Fov, Veto,
+};
+use crate::{calc, channels, logging, power, report, simulate, CrbError};
+
+/// Calculate the CRB, and any of the optional extras, in every channel. Each
+/// channel's result is written to `config.output` when it is done; with
+/// `resume`, channels already there are read back instead of recalculated.
+pub fn run_crb(config: &Config, resume: bool) -> Result<(), CrbError> {
+    let metafits = MetafitsContext::new(&config.metafits, None)?;
+    let observation = Observation::new(config, &metafits);
+    let phase_centre = observation.phase_centre;
+
+    let rms_re = calc::calc_rms(
+        config.t_sys,
+        observation.channel_width,
+        observation.int_time,
+        config.telescope,
+    );
+    // The noise level of a channel with this many selected baselines, which is
+    // also its flux veto threshold.
+    let channel_rms = |num_baselines: usize| 5.0 * (rms_re / (num_baselines as f64).sqrt());
+
+    let uv_range = config.uv_range.clone().unwrap_or_default();
+
+    let baselines_xy = calc::create_baselines(&metafits);
+
+    let averaging = calc::Averaging {
+        channel_width: observation.channel_width,
+        int_time: observation.int_time,
+    };
+
+    let channels = channels::get_channels(config, &metafits, observation.channel_width)?;
+    info!("Number of channels: {}", channels.len());
+
+    // Drop only the components that no channel would use: the field of view
+    // is widest at the lowest frequency, and the flux threshold lowest with
+    // every baseline selected. The power spectrum uses faint components too.
+    let num_ants = baselines_xy.len_of(Axis(0));
+    let lowest_freq = channels
+        .iter()
+        .map(|channel| channel.freq)
+        .fold(f64::INFINITY, f64::min);
+    let veto = Veto {
+        min_flux: match config.power_spectrum {
+            Some(_) => None,
+            None => Some(channel_rms(num_ants * (num_ants - 1) / 2)),
+        },
+        fov: Some(Fov {
+            phase_centre,
+            lambda: physical_constants::SPEED_OF_LIGHT_IN_VACUUM / lowest_freq,
+            diameter: config.diameter(),
+        }),
+    };
+
+    let component_list: ComponentList = match &config.synthetic_sky {
+        Some(synthetic_sky) => {
+            let source_list = generate::generate_source_list(synthetic_sky, phase_centre)?;
+            info!(
+                "Generated {} synthetic sources, writing to {}",
+                source_list.len(),
+                synthetic_sky.output
+            );
+            write::source_list_to_file(&synthetic_sky.output, &source_list)?;
+            let mut component_list = ComponentList::new(source_list);
+            component_list.veto(&veto);
+            component_list
+        }
+        None => {
+            let srclist = config
+                .srclist
+                .as_ref()
+                .ok_or(config::ConfigError::NoSkyModel)?;
+            read::component_list_from_file(srclist, Some(observation.lst), &veto)?
+        }
+    };
+
+    info!(
+        "Number of components after the initial veto: {}",
+        &component_list.len()
+    );
+
+    let redundant_groups = config
+        .redundant_calibration
+        .as_ref()
+        .map(|redundant| calc::redundant::redundant_groups(&baselines_xy, redundant.tolerance));
+    if let Some(groups) = &redundant_groups {
+        info!(
+            "Redundant groups: {} covering {} baselines",
+            groups.len(),
+            groups.iter().map(|g| g.baselines.len()).sum::<usize>()
+        );
+        if groups.is_empty() {
+            warn!("No baselines are redundant within the tolerance");
+        }
+    }
+
+    let (mut writer, completed) = ResultWriter::open(&config.output, &config.hash, resume)?;
+    if resume {
+        info!(
+            "Resuming with {} channels already in {}",
+            completed.len(),
+            config.output
+        );
+    }
+
+    // The spectral basis and the power spectrum need every channel's Fisher
+    // matrix, so these are kept (and written out for resuming).
+    let keep_fishers = config.spectral_basis.is_some() || config.power_spectrum.is_some();
+    let mut channel_crbs = vec![];
+    let mut channel_fishers = vec![];
+
+    let progress = logging::progress_bar(channels.len(), "channels");
+    for channel in channels.iter() {
+        progress.inc(1);
+        if let Some(result) = completed.iter().find(|result| result.is_for(channel)) {
+            debug!("Skipping {}, already done", channel);
+            channel_crbs.push(Array1::from(result.crb.clone()));
+            if keep_fishers {
+                let fisher = result.fisher().ok_or(OutputError::MissingFisher {
+                    path: config.output.clone(),
+                    freq: channel.freq,
+                })?;
+                channel_fishers.push(fisher);
+            }
+            continue;
+        }
+
+        let start_time = Instant::now();
+        debug!("Channel {}", channel);
+
+        let freq = channel.freq;
+        let lambda = physical_constants::SPEED_OF_LIGHT_IN_VACUUM / freq;
+        let selection = uv_range.selection(&baselines_xy, lambda);
+        if !selection
+            .indexed_iter()
+            .any(|((a, b), &selected)| a < b && selected)
+        {
+            return Err(channels::ChannelError::NoBaselines { freq }.into());
+        }
+        let num_baselines = calc::num_selected_baselines(&selection);
+        let rms_vis: f64 = channel_rms(num_baselines);
+
+        let autocorrelation_noise = config.autocorrelations.as_ref().map(|auto| auto.noise);
+
+        let freq_comp_list = component_list.vetoed(&Veto {
+            min_flux: Some(rms_vis),
+            fov: Some(Fov {
+                phase_centre,
+                lambda,
+                diameter: config.diameter(),
+            }),
+        });
+        debug!(
+            "Number of components after flux {} and field-of-view veto ({} baselines): {}",
+            rms_vis,
+            num_baselines,
+            &freq_comp_list.len()
+        );
+
+        debug!("Calculating CRB");
+        let fisher = calc::fisher_matrix(
+            &baselines_xy,
+            &freq_comp_list,
+            lambda,
+            freq,
+            rms_vis,
+            phase_centre,
+            averaging,
+            &selection,
+            autocorrelation_noise,
+        );
+        let num_ants = fisher.nrows();
+        if config.diagnostics {
+            info!("{}", calc::diagnostics::diagnose(&fisher)?);
+        }
+        // The gain amplitudes have no degeneracy, so the gauge is only needed
+        // for the phases below.
+        let crb = calc::calculate_crb(&fisher, &Gauge::Inverse, &Array2::zeros((num_ants, 0)))?;
+        channel_crbs.push(crb.matrix.diag().mapv(|c| c.re));
+
+        let result = ChannelResult {
+            freq,
+            coarse_chan: channel.coarse_chan,
+            fine_chan: channel.fine_chan,
+            num_baselines,
+            sigma: rms_vis,
+            num_components: freq_comp_list.len(),
+            crb: crb.matrix.diag().iter().map(|c| c.re).collect(),
+            fisher: keep_fishers.then(|| fisher.iter().map(|f| f.re).collect()),
+        };
+
+        if config.phases {
+            debug!("Calculating phase CRB");
+            let overall_phase = Array2::<Complex64>::ones((num_ants, 1));
+            let phase_crb =
+                calc::calculate_crb(&calc::phase_fisher(&fisher), &config.gauge, &overall_phase)?;
+            report::report_null_space(&phase_crb.null_space);
+            info!(
+                "Mean phase CRB: {}",
+                phase_crb.matrix.diag().mapv(|c| c.re).mean().unwrap()
+            );
+        }
+
+        if keep_fishers {
+            channel_fishers.push(fisher);
+        }
+
+        if config.full_polarisation {
+            debug!("Calculating full-polarisation CRB");
+            let fisher = calc::polarisation::full_pol_fisher(
+                &baselines_xy,
+                &freq_comp_list,
+                lambda,
+                freq,
+                rms_vis,
+                phase_centre,
+                averaging,
+                &selection,
+            );
+            let (jones_crb, num_degenerate) = calc::polarisation::jones_crb(&fisher)?;
+            info!("Degenerate Jones directions: {}", num_degenerate);
+            for (name, crb) in calc::polarisation::JONES_PARAMETERS
+                .iter()
+                .zip(jones_crb.axis_iter(Axis(1)))
+            {
+                info!("Mean CRB of {}: {}", name, crb.mean().unwrap());
+            }
+        }
+
+        if let Some(num_directions) = config.num_directions {
+            debug!("Calculating direction-dependent CRB");
+            let clusters = cluster_components(&freq_comp_list, num_directions, phase_centre, freq);
+            let fisher = calc::direction::direction_dependent_fisher(
+                &baselines_xy,
+                &clusters,
+                lambda,
+                freq,
+                rms_vis,
+                phase_centre,
+                averaging,
+                &selection,
+            );
+            let dd_crb = calc::direction::direction_crb(&fisher, clusters.len())?;
+            info!(
+                "{:>5} {:>12} {:>12} {:>6} {:>12} {:>14}",
+                "dir", "l", "m", "comps", "flux", "mean CRB"
+            );
+            for (d, (cluster, crb)) in clusters.iter().zip(dd_crb.axis_iter(Axis(1))).enumerate() {
+                let fluxes: Vec<(f64, f64, f64)> = cluster
+                    .iter()
+                    .map(|comp| {
+                        let lmn = comp.radec.to_lmn(phase_centre);
+                        (comp.estimate_at_freq(freq).i, lmn.l, lmn.m)
+                    })
+                    .collect();
+                let flux: f64 = fluxes.iter().map(|f| f.0).sum();
+                let l = fluxes.iter().map(|f| f.0 * f.1).sum::<f64>() / flux;
+                let m = fluxes.iter().map(|f| f.0 * f.2).sum::<f64>() / flux;
+                info!(
+                    "{:>5} {:>12.6} {:>12.6} {:>6} {:>12.4} {:>14.6e}",
+                    d,
+                    l,
+                    m,
+                    cluster.len(),
+                    flux,
+                    crb.mean().unwrap()
+                );
+            }
+        }
+
+        if let Some(offset_groups) = &config.ionospheric_offsets {
+            debug!("Calculating CRB with ionospheric offsets");
+            let groups = match offset_groups {
+                config::OffsetGroups::Components => singletons(&freq_comp_list),
+                config::OffsetGroups::Clusters(num_clusters) => {
+                    cluster_components(&freq_comp_list, *num_clusters, phase_centre, freq)
+                }
+            };
+            let fisher = calc::ionosphere::ionosphere_fisher(
+                &baselines_xy,
+                &groups,
+                lambda,
+                freq,
+                rms_vis,
+                phase_centre,
+                averaging,
+                &selection,
+            )?;
+            let iono_crb = calc::ionosphere::ionosphere_crb(&fisher, baselines_xy.len_of(Axis(0)))?;
+            let fixed = iono_crb.gains_fixed.mean().unwrap();
+            let joint = iono_crb.gains_joint.mean().unwrap();
+            info!(
+                "Mean gain CRB: {} with offsets known, {} with offsets estimated ({:.4}x)",
+                fixed,
+                joint,
+                joint / fixed
+            );
+            info!(
+                "{:>6} {:>6} {:>14} {:>14}",
+                "group", "comps", "std dl [\"]", "std dm [\"]"
+            );
+            for (g, (group, crb)) in groups
+                .iter()
+                .zip(iono_crb.offsets.axis_iter(Axis(0)))
+                .enumerate()
+            {
+                info!(
+                    "{:>6} {:>6} {:>14.6e} {:>14.6e}",
+                    g,
+                    group.len(),
+                    crb[0].sqrt().to_degrees() * 3600.0,
+                    crb[1].sqrt().to_degrees() * 3600.0
+                );
+            }
+        }
+
+        if let Some(num_free) = config.free_fluxes {
+            debug!("Calculating CRB with {} free fluxes", num_free);
+            let free = calc::sky::brightest(&freq_comp_list, num_free, freq);
+            let fisher = calc::sky::sky_gain_fisher(
+                &baselines_xy,
+                &freq_comp_list,
+                &free,
+                lambda,
+                freq,
+                rms_vis,
+                phase_centre,
+                averaging,
+                &selection,
+            );
+            let sky_crb = calc::sky::marginal_gain_crb(&fisher, baselines_xy.len_of(Axis(0)))?;
+            let known = sky_crb.gains_known.mean().unwrap();
+            let marginal = sky_crb.gains_marginal.mean().unwrap();
+            info!(
+                "Mean gain CRB: {} with fluxes known, {} with fluxes marginalised ({:.4}x)",
+                known,
+                marginal,
+                marginal / known
+            );
+            info!(
+                "{:>6} {:>12} {:>14} {:>14}",
+                "comp", "flux [Jy]", "std [Jy]", "std / flux"
+            );
+            for (&c, crb) in free.iter().zip(sky_crb.fluxes.iter()) {
+                let flux = freq_comp_list[c].estimate_at_freq(freq).i;
+                info!(
+                    "{:>6} {:>12.4} {:>14.6e} {:>14.6e}",
+                    c,
+                    flux,
+                    crb.sqrt(),
+                    crb.sqrt() / flux
+                );
+            }
+        }
+
+        if let Some(groups) = &redundant_groups {
+            debug!("Calculating redundant-calibration CRB");
+            let model = calc::model_visibilities(
+                &baselines_xy,
+                &freq_comp_list,
+                lambda,
+                freq,
+                phase_centre,
+                averaging,
+            );
+            match calc::redundant::redundant_crb(
+                &baselines_xy,
+                groups,
+                &model,
+                rms_vis,
+                &selection,
+            )? {
+                Some(redundant_crb) => {
+                    let sky_crb = redundant_crb
+                        .antennas
+                        .iter()
+                        .map(|&a| crb.matrix[[a, a]].re)
+                        .sum::<f64>()
+                        / redundant_crb.antennas.len() as f64;
+                    info!(
+                        "Mean CRB over {} antennas: redundant amplitude {}, redundant phase {}, sky-based {}",
+                        redundant_crb.antennas.len(),
+                        redundant_crb.amplitude.mean().unwrap(),
+                        redundant_crb.phase.mean().unwrap(),
+                        sky_crb
+                    );
+                    info!(
+                        "Mean CRB of redundant visibilities: {}",
+                        redundant_crb.visibility.mean().unwrap()
+                    );
+                }
+                None => info!("No redundant baselines; skipping the redundant-calibration CRB"),
+            }
+        }
+
+        if let Some(monte_carlo) = &config.monte_carlo {
+            debug!("Running Monte Carlo validation");
+            let model = calc::model_visibilities(
+                &baselines_xy,
+                &freq_comp_list,
+                lambda,
+                freq,
+                phase_centre,
+                averaging,
+            );
+            let result = simulate::run_monte_carlo(&model, &selection, rms_vis, monte_carlo)?;
+            simulate::report(&crb.matrix, &result);
+        }
+
+        writer.write(&result)?;
+
+        let end_time = start_time.elapsed();
+        debug!("This block took: {:?}", end_time);
+    }
+    progress.finish_and_clear();
+    info!("Channel results written to {}", writer.path().display());
+
+    let freqs: Vec<f64> = channels.iter().map(|c| c.freq).collect();
+    let mut spectral_crb = None;
+    if let Some(basis) = &config.spectral_basis {
+        info!("Calculating joint CRB with a {:?} spectral basis", basis);
+        let joint_crb = calc::spectral::calculate_spectral_crb(&channel_fishers, &freqs, basis)?;
+
+        let coefficient_crbs = joint_crb.coefficients.diag().mapv(|c| c.re);
+        for k in 0..basis.num_functions() {
+            let mean = coefficient_crbs
+                .iter()
+                .skip(k)
+                .step_by(basis.num_functions())
+                .sum::<f64>()
+                / (coefficient_crbs.len() / basis.num_functions()) as f64;
+            info!("Mean CRB of basis coefficient {}: {}", k, mean);
+        }
+
+        info!(
+            "{:>40} {:>14} {:>14}",
+            "channel", "channel CRB", "joint CRB"
+        );
+        for ((channel, crb), joint) in channels
+            .iter()
+            .zip(channel_crbs.iter())
+            .zip(joint_crb.bandpass.axis_iter(Axis(0)))
+        {
+            info!(
+                "{:>40} {:>14.6e} {:>14.6e}",
+                channel.to_string(),
+                crb.mean().unwrap(),
+                joint.mean().unwrap()
+            );
+        }
+        spectral_crb = Some((basis.evaluate(&freqs), joint_crb));
+    }
+
+    if let Some(power_spectrum) = &config.power_spectrum {
+        info!("Calculating delay power spectrum of calibration errors");
+        let max_freq = freqs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let foregrounds = component_list.vetoed(&Veto {
+            min_flux: None,
+            fov: Some(Fov {
+                phase_centre,
+                lambda: physical_constants::SPEED_OF_LIGHT_IN_VACUUM / max_freq,
+                diameter: config.diameter(),
+            }),
+        });
+        let centre_freq = (freqs[0] + freqs[freqs.len() - 1]) / 2.0;
+        let brightest =
+            calc::sky::brightest(&foregrounds, power_spectrum.max_components, centre_freq);
+        let foregrounds: Vec<&Component> = brightest.iter().map(|&c| foregrounds[c]).collect();
+
+        let num_ants = baselines_xy.len_of(Axis(0));
+        let pairs: Vec<(usize, usize)> = (0..num_ants)
+            .flat_map(|a| ((a + 1)..num_ants).map(move |b| (a, b)))
+            .collect();
+        let foreground_vis = power::foreground_visibilities(
+            &baselines_xy,
+            &foregrounds,
+            &pairs,
+            &freqs,
+            phase_centre,
+            averaging,
+        );
+
+        let channel_covariances = channel_fishers
+            .iter()
+            .map(|fisher| {
+                let num_ants = fisher.nrows();
+                calc::calculate_crb(fisher, &Gauge::Inverse, &Array2::zeros((num_ants, 0)))
+                    .map(|crb| crb.matrix)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let covariance = match &spectral_crb {
+            Some((basis, joint_crb)) => power::GainCovariance::Spectral {
+                basis,
+                coefficients: &joint_crb.coefficients,
+            },
+            None => power::GainCovariance::Independent(&channel_covariances),
+        };
+        let spectrum = power::delay_spectrum(
+            &baselines_xy,
+            &pairs,
+            &foreground_vis,
+            &freqs,
+            observation.channel_width,
+            &covariance,
+            power_spectrum.num_kperp_bins,
+        );
+        power::write_delay_spectrum(&spectrum, &power_spectrum.output)?;
+        info!(
+            "Peak contamination {:e} Jy^2 Hz^2, written to {}_1d.txt and {}_2d.txt",
+            spectrum.power_1d.iter().copied().fold(0.0, f64::max),
+            power_spectrum.output,
+            power_spectrum.output
+        );
+    }
+
+    return Ok(());
+}
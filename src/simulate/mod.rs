@@ -0,0 +1,273 @@
+//! Monte Carlo validation of the CRB.
+//!
+//! Noisy visibilities are simulated from a sky model with known random
+//! antenna gains, calibrated with StefCal, and the scatter of the recovered
+//! gains over many realisations is compared with the bound from
+//! [`crate::calc::calculate_crb`].
+
+use crate::config::{ConfigError, MonteCarloConfig};
+use log::info;
+use ndarray::prelude::*;
+use num_complex::*;
+use rand::prelude::*;
+use rand_distr::Normal;
+use rayon::prelude::*;
+
+/// Maximum number of StefCal iterations per realisation.
+const MAX_ITERATIONS: usize = 200;
+
+/// StefCal stops once the relative change in the gains drops below this.
+const STOP_THRESHOLD: f64 = 1e-10;
+
+pub struct MonteCarloResult {
+    /// Empirical variance of the recovered log-amplitude of each antenna gain
+    pub variance: Array1<f64>,
+
+    /// Number of realisations in which StefCal converged
+    pub num_converged: usize,
+}
+
+/// Solve for the antenna gains `g` in `V = G M G^H` with StefCal (Salvini &
+/// Wijnholds 2014), using only the baselines in `selection`. Autocorrelations
+/// are ignored. Returns `None` if the solution did not converge.
+pub fn stefcal(
+    vis: &Array2<Complex64>,
+    model: &Array2<Complex64>,
+    selection: &Array2<bool>,
+) -> Option<Array1<Complex64>> {
+    let num_ants = vis.nrows();
+    let mut gains = Array1::<Complex64>::ones(num_ants);
+
+    for iter in 0..MAX_ITERATIONS {
+        let mut new_gains = Array1::<Complex64>::zeros(num_ants);
+        for a in 0..num_ants {
+            let mut numerator = Complex64::new(0.0, 0.0);
+            let mut denominator: f64 = 0.0;
+            for b in 0..num_ants {
+                if a == b || !selection[[a, b]] {
+                    continue;
+                }
+                let z = model[[a, b]] * gains[b].conj();
+                numerator += z.conj() * vis[[a, b]];
+                denominator += z.norm_sqr();
+            }
+            new_gains[a] = numerator / denominator;
+        }
+
+        // Averaging every second iteration stops the solution oscillating.
+        if iter % 2 == 1 {
+            new_gains = (&new_gains + &gains) / Complex64::new(2.0, 0.0);
+        }
+
+        let change: f64 = (&new_gains - &gains).mapv(|g| g.norm_sqr()).sum().sqrt();
+        let norm: f64 = new_gains.mapv(|g| g.norm_sqr()).sum().sqrt();
+        gains = new_gains;
+
+        if change / norm < STOP_THRESHOLD {
+            return Some(gains);
+        }
+    }
+
+    return None;
+}
+
+/// Simulate `config.num_realisations` sets of calibrated gains and measure the
+/// variance of their log-amplitudes. Each realisation draws new gains and
+/// new noise with a standard deviation of `sigma` (for the complex
+/// visibility, i.e. `sigma / sqrt(2)` per real component). Like the CRB, only
+/// the baselines in `selection` are calibrated on.
+pub fn run_monte_carlo(
+    model: &Array2<Complex64>,
+    selection: &Array2<bool>,
+    sigma: f64,
+    config: &MonteCarloConfig,
+) -> Result<MonteCarloResult, ConfigError> {
+    let num_ants = model.nrows();
+    let normal = |mean: f64, std: f64, field: &'static str, value: f64| {
+        return Normal::new(mean, std).map_err(|_| ConfigError::Distribution { field, value });
+    };
+    let amp_dist = normal(
+        1.0,
+        config.gain_amp_std,
+        "monte_carlo.gain_amp_std",
+        config.gain_amp_std,
+    )?;
+    let phase_dist = normal(
+        0.0,
+        config.gain_phase_std.to_radians(),
+        "monte_carlo.gain_phase_std",
+        config.gain_phase_std,
+    )?;
+    let noise_dist = normal(0.0, sigma / 2.0f64.sqrt(), "sigma", sigma)?;
+
+    let errors: Vec<Array1<f64>> = (0..config.num_realisations)
+        .into_par_iter()
+        .filter_map(|r| {
+            // Seed each realisation separately so results don't depend on
+            // how rayon schedules them.
+            let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(r as u64));
+
+            let true_gains: Array1<Complex64> = (0..num_ants)
+                .map(|_| {
+                    Complex64::from_polar(amp_dist.sample(&mut rng), phase_dist.sample(&mut rng))
+                })
+                .collect();
+
+            let mut vis = Array2::<Complex64>::zeros((num_ants, num_ants));
+            for a in 0..num_ants {
+                for b in (a + 1)..num_ants {
+                    let noise =
+                        Complex64::new(noise_dist.sample(&mut rng), noise_dist.sample(&mut rng));
+                    vis[[a, b]] = true_gains[a] * model[[a, b]] * true_gains[b].conj() + noise;
+                    vis[[b, a]] = vis[[a, b]].conj();
+                }
+            }
+
+            let gains = stefcal(&vis, model, selection)?;
+            return Some(
+                gains
+                    .iter()
+                    .zip(true_gains.iter())
+                    .map(|(g, g_true)| g.norm().ln() - g_true.norm().ln())
+                    .collect(),
+            );
+        })
+        .collect();
+
+    let num_converged = errors.len();
+    let mut variance = Array1::<f64>::zeros(num_ants);
+    if num_converged > 1 {
+        let errors = ndarray::stack(
+            Axis(0),
+            &errors.iter().map(|e| e.view()).collect::<Vec<_>>(),
+        )
+        .unwrap();
+        variance = errors.var_axis(Axis(0), 1.0);
+    }
+
+    return Ok(MonteCarloResult {
+        variance,
+        num_converged,
+    });
+}
+
+/// Log the bound next to the Monte Carlo variance for each antenna.
+pub fn report(crb: &Array2<Complex64>, result: &MonteCarloResult) {
+    info!(
+        "Monte Carlo: {} realisations converged",
+        result.num_converged
+    );
+    info!(
+        "{:>5} {:>14} {:>14} {:>8}",
+        "ant", "CRB", "variance", "ratio"
+    );
+    for (a, variance) in result.variance.iter().enumerate() {
+        let bound = crb[[a, a]].re;
+        info!(
+            "{:>5} {:>14.6e} {:>14.6e} {:>8.3}",
+            a,
+            bound,
+            variance,
+            variance / bound
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NUM_ANTS: usize = 4;
+
+    fn model() -> Array2<Complex64> {
+        return Array2::from_shape_fn((NUM_ANTS, NUM_ANTS), |(a, b)| {
+            Complex64::from_polar(1.0 + 0.1 * (a + b) as f64, 0.3 * (a as f64 - b as f64))
+        });
+    }
+
+    fn true_gains() -> Array1<Complex64> {
+        return array![
+            Complex64::from_polar(1.1, 0.2),
+            Complex64::from_polar(0.9, -0.4),
+            Complex64::from_polar(1.05, 1.0),
+            Complex64::from_polar(0.95, 0.0),
+        ];
+    }
+
+    fn visibilities(gains: &Array1<Complex64>, model: &Array2<Complex64>) -> Array2<Complex64> {
+        return Array2::from_shape_fn((NUM_ANTS, NUM_ANTS), |(a, b)| {
+            gains[a] * model[[a, b]] * gains[b].conj()
+        });
+    }
+
+    /// Gains are only determined up to an overall phase, so compare the
+    /// products `g_a g_b^*`.
+    fn assert_gains_match(gains: &Array1<Complex64>, expected: &Array1<Complex64>) {
+        for a in 0..NUM_ANTS {
+            for b in 0..NUM_ANTS {
+                let product = gains[a] * gains[b].conj();
+                let expected = expected[a] * expected[b].conj();
+                assert!((product - expected).norm() < 1e-8, "{gains} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn stefcal_recovers_noiseless_gains() {
+        let model = model();
+        let selection = Array2::from_elem((NUM_ANTS, NUM_ANTS), true);
+        let gains = stefcal(&visibilities(&true_gains(), &model), &model, &selection).unwrap();
+        assert_gains_match(&gains, &true_gains());
+    }
+
+    #[test]
+    fn stefcal_ignores_unselected_baselines() {
+        let model = model();
+        let mut vis = visibilities(&true_gains(), &model);
+        vis[[0, 1]] = Complex64::new(100.0, -50.0);
+        vis[[1, 0]] = vis[[0, 1]].conj();
+        let mut selection = Array2::from_elem((NUM_ANTS, NUM_ANTS), true);
+        selection[[0, 1]] = false;
+        selection[[1, 0]] = false;
+
+        let gains = stefcal(&vis, &model, &selection).unwrap();
+        assert_gains_match(&gains, &true_gains());
+    }
+
+    #[test]
+    fn monte_carlo_variance_matches_crb() {
+        // With a unit model, F = 2 / sigma^2 ((n - 2) I + J), whose inverse has
+        // diagonal sigma^2 / 2 * (1 - 1 / (2n - 2)) / (n - 2).
+        let sigma = 0.05;
+        let n = NUM_ANTS as f64;
+        let crb = sigma.powi(2) / 2.0 * (1.0 - 1.0 / (2.0 * n - 2.0)) / (n - 2.0);
+
+        let config = MonteCarloConfig {
+            num_realisations: 2000,
+            seed: 1,
+            gain_amp_std: 0.0,
+            gain_phase_std: 0.0,
+        };
+        let model = Array2::ones((NUM_ANTS, NUM_ANTS));
+        let selection = Array2::from_elem((NUM_ANTS, NUM_ANTS), true);
+        let result = run_monte_carlo(&model, &selection, sigma, &config).unwrap();
+
+        assert_eq!(result.num_converged, config.num_realisations);
+        for variance in result.variance.iter() {
+            assert!((variance / crb - 1.0).abs() < 0.15, "{variance} vs {crb}");
+        }
+    }
+
+    #[test]
+    fn monte_carlo_rejects_invalid_spread() {
+        let config = MonteCarloConfig {
+            num_realisations: 1,
+            seed: 0,
+            gain_amp_std: f64::NAN,
+            gain_phase_std: 0.0,
+        };
+        let model = Array2::ones((NUM_ANTS, NUM_ANTS));
+        let selection = Array2::from_elem((NUM_ANTS, NUM_ANTS), true);
+        assert!(run_monte_carlo(&model, &selection, 0.1, &config).is_err());
+    }
+}
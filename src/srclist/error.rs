@@ -4,20 +4,24 @@
 
 use thiserror::Error;
 
-// use crate::{io::GlobError, srclist::HYPERDRIVE_SOURCE_LIST_FILE_TYPES_COMMA_SEPARATED};
 use crate::srclist::HYPERDRIVE_SOURCE_LIST_FILE_TYPES_COMMA_SEPARATED;
 
 /// Errors associated with reading in any kind of source list.
 #[derive(Error, Debug)]
-pub(crate) enum ReadSourceListError {
+pub enum ReadSourceListError {
     #[error(
         "Source list error: Attempted to use RA {0}°, but this is out of range (0° <= RA < 360°)"
     )]
     InvalidRa(f64),
 
-    #[error("Source list error: Attempted to use HA {0}, but this is out of range (0 < HA < 24)")]
+    #[error(
+        "Source list error: Attempted to use HA {0}, but this is out of range (-24 < HA < 24)"
+    )]
     InvalidHa(f64),
 
+    #[error("Source list error: Components given by hour angle need the LST of an observation (e.g. from a metafits file)")]
+    NoLst,
+
     #[error(
         "Source list error: Attempted to use Dec {0}°, but this is out of range (-90° <= Dec <= 90°)"
     )]
@@ -53,12 +57,34 @@ pub(crate) enum ReadSourceListError {
     #[error("Tried to use {requested} sources, but only {available} sources were available after vetoing")]
     VetoTooFewSources { requested: usize, available: usize },
 
+    #[error("Source list file extension '{0}' is not recognised; supported: {}", *HYPERDRIVE_SOURCE_LIST_FILE_TYPES_COMMA_SEPARATED)]
+    UnknownFileType(String),
+
     #[error(transparent)]
     Yaml(#[from] serde_yaml::Error),
 
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
     #[error(transparent)]
     Sexagesimal(#[from] marlu::sexagesimal::SexagesimalError),
 
     #[error(transparent)]
     IO(#[from] std::io::Error),
 }
+
+/// Errors associated with writing out a source list.
+#[derive(Error, Debug)]
+pub enum WriteSourceListError {
+    #[error("Cannot tell what type of source list to write to {0}; supported: {}", *HYPERDRIVE_SOURCE_LIST_FILE_TYPES_COMMA_SEPARATED)]
+    UnknownFileType(String),
+
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
@@ -0,0 +1,235 @@
+//! Draw a random sky model from a differential source-count law,
+//! `dN/dS = norm * S^-slope`, so that sky-model completeness can be explored
+//! without relying on any particular catalogue.
+
+use std::f64::consts::{PI, TAU};
+
+use marlu::RADec;
+use rand::prelude::*;
+use rand_distr::{Normal, Poisson};
+
+use crate::config::{ConfigError, SyntheticSkyConfig};
+use crate::srclist::{
+    ComponentType, FluxDensity, FluxDensityType, Source, SourceComponent, SourceList,
+};
+
+/// Generate a [`SourceList`] of point sources. Positions are uniform over the
+/// whole sky, or over a cap of `config.radius` degrees around
+/// `phase_centre`. If clustering is requested, that fraction of the sources
+/// is scattered around randomly placed cluster centres instead.
+pub fn generate_source_list(
+    config: &SyntheticSkyConfig,
+    phase_centre: RADec,
+) -> Result<SourceList, ConfigError> {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let radius = config.radius.map_or(PI, f64::to_radians);
+    let solid_angle = TAU * (1.0 - radius.cos());
+    let expected = solid_angle * config.norm * integrated_counts(config);
+
+    let num_sources = if expected > 0.0 {
+        Poisson::new(expected)
+            .map_err(|_| ConfigError::Distribution {
+                field: "synthetic_sky.norm",
+                value: config.norm,
+            })?
+            .sample(&mut rng) as usize
+    } else {
+        0
+    };
+
+    let si_dist =
+        Normal::new(config.spectral_index_mean, config.spectral_index_std).map_err(|_| {
+            ConfigError::Distribution {
+                field: "synthetic_sky.spectral_index_std",
+                value: config.spectral_index_std,
+            }
+        })?;
+    let scatter = match &config.clustering {
+        Some(clustering) => Some(Normal::new(0.0, clustering.radius.to_radians()).map_err(
+            |_| ConfigError::Distribution {
+                field: "synthetic_sky.clustering.radius",
+                value: clustering.radius,
+            },
+        )?),
+        None => None,
+    };
+
+    // Decide up front which sources are clustered so the number of cluster
+    // centres can be set from the mean cluster size.
+    let (num_clustered, centres) = match &config.clustering {
+        Some(clustering) => {
+            let num_clustered = (clustering.fraction * num_sources as f64).round() as usize;
+            let num_centres =
+                ((num_clustered as f64 / clustering.mean_members).ceil() as usize).max(1);
+            let centres: Vec<RADec> = (0..num_centres)
+                .map(|_| random_in_cap(&mut rng, phase_centre, radius))
+                .collect();
+            (num_clustered, centres)
+        }
+        None => (0, vec![]),
+    };
+
+    let mut source_list = SourceList::new();
+    for n in 0..num_sources {
+        let radec = match &scatter {
+            Some(scatter) if n < num_clustered => {
+                let centre = centres[rng.gen_range(0..centres.len())];
+                let (dx, dy): (f64, f64) = (scatter.sample(&mut rng), scatter.sample(&mut rng));
+                offset(centre, dx.hypot(dy), dy.atan2(dx))
+            }
+            _ => random_in_cap(&mut rng, phase_centre, radius),
+        };
+
+        let comp = SourceComponent {
+            radec,
+            comp_type: ComponentType::Point,
+            flux_type: FluxDensityType::PowerLaw {
+                si: si_dist.sample(&mut rng),
+                fd: FluxDensity {
+                    freq: config.freq,
+                    i: random_flux(&mut rng, config),
+                    ..Default::default()
+                },
+            },
+        };
+
+        source_list.insert(
+            format!("synthetic_{n:07}"),
+            Source {
+                components: vec![comp].into_boxed_slice(),
+            },
+        );
+    }
+
+    return Ok(source_list);
+}
+
+/// The integral of `S^-slope` over the configured flux range.
+fn integrated_counts(config: &SyntheticSkyConfig) -> f64 {
+    let (s_min, s_max, slope) = (config.flux_min, config.flux_max, config.slope);
+    if (slope - 1.0).abs() < f64::EPSILON {
+        return (s_max / s_min).ln();
+    }
+    return (s_max.powf(1.0 - slope) - s_min.powf(1.0 - slope)) / (1.0 - slope);
+}
+
+/// Draw a flux density from the source-count law by inverting its CDF.
+fn random_flux<R: Rng>(rng: &mut R, config: &SyntheticSkyConfig) -> f64 {
+    let (s_min, s_max, slope) = (config.flux_min, config.flux_max, config.slope);
+    let u: f64 = rng.gen();
+    if (slope - 1.0).abs() < f64::EPSILON {
+        return s_min * (s_max / s_min).powf(u);
+    }
+    let a = s_min.powf(1.0 - slope);
+    let b = s_max.powf(1.0 - slope);
+    return (a + u * (b - a)).powf(1.0 / (1.0 - slope));
+}
+
+/// A position drawn uniformly (in solid angle) within `radius` radians of
+/// `centre`.
+fn random_in_cap<R: Rng>(rng: &mut R, centre: RADec, radius: f64) -> RADec {
+    let cos_sep: f64 = rng.gen_range(radius.cos()..=1.0);
+    let bearing: f64 = rng.gen_range(0.0..TAU);
+    return offset(centre, cos_sep.acos(), bearing);
+}
+
+/// The position `sep` radians away from `centre` along position angle
+/// `bearing` (measured from north through east).
+fn offset(centre: RADec, sep: f64, bearing: f64) -> RADec {
+    let (sin_dec0, cos_dec0) = centre.dec.sin_cos();
+    let dec = (sin_dec0 * sep.cos() + cos_dec0 * sep.sin() * bearing.cos()).asin();
+    let ra =
+        centre.ra + (bearing.sin() * sep.sin() * cos_dec0).atan2(sep.cos() - sin_dec0 * dec.sin());
+    return RADec::from_radians(ra.rem_euclid(TAU), dec);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClusteringConfig;
+
+    fn config(seed: u64) -> SyntheticSkyConfig {
+        return SyntheticSkyConfig {
+            output: String::new(),
+            seed,
+            norm: 1e4,
+            slope: 1.6,
+            flux_min: 0.01,
+            flux_max: 10.0,
+            freq: 150e6,
+            spectral_index_mean: -0.8,
+            spectral_index_std: 0.2,
+            radius: Some(5.0),
+            clustering: None,
+        };
+    }
+
+    fn fluxes(source_list: &SourceList) -> Vec<f64> {
+        return source_list
+            .values()
+            .flat_map(|source| source.components.iter())
+            .map(|comp| comp.estimate_at_freq(150e6).i)
+            .collect();
+    }
+
+    #[test]
+    fn sources_follow_the_counts_inside_the_cap() {
+        let config = config(1);
+        let phase_centre = RADec::from_degrees(30.0, -27.0);
+        let source_list = generate_source_list(&config, phase_centre).unwrap();
+
+        let radius = config.radius.unwrap().to_radians();
+        let expected = TAU * (1.0 - radius.cos()) * config.norm * integrated_counts(&config);
+        let num_sources = source_list.len() as f64;
+        assert!(
+            (num_sources - expected).abs() < 5.0 * expected.sqrt(),
+            "{num_sources} sources, expected {expected}"
+        );
+
+        for comp in source_list.values().flat_map(|s| s.components.iter()) {
+            assert!(comp.radec.separation(phase_centre) <= radius + 1e-12);
+        }
+        let fluxes = fluxes(&source_list);
+        assert!(fluxes
+            .iter()
+            .all(|&s| (config.flux_min..=config.flux_max).contains(&s)));
+        // Most sources are near the faint end of a steep count law.
+        let faint = fluxes.iter().filter(|&&s| s < 0.1).count();
+        assert!(faint > fluxes.len() / 2);
+    }
+
+    #[test]
+    fn same_seed_same_sky() {
+        let phase_centre = RADec::from_degrees(30.0, -27.0);
+        let a = generate_source_list(&config(7), phase_centre).unwrap();
+        let b = generate_source_list(&config(7), phase_centre).unwrap();
+        let c = generate_source_list(&config(8), phase_centre).unwrap();
+        assert_eq!(fluxes(&a), fluxes(&b));
+        assert_ne!(fluxes(&a), fluxes(&c));
+    }
+
+    #[test]
+    fn clustered_sources_stay_near_a_centre() {
+        let mut config = config(3);
+        config.clustering = Some(ClusteringConfig {
+            fraction: 1.0,
+            radius: 0.1,
+            mean_members: 1e6,
+        });
+        let phase_centre = RADec::from_degrees(30.0, -27.0);
+        let source_list = generate_source_list(&config, phase_centre).unwrap();
+
+        // With one cluster centre, every source is within a few scatter
+        // radii of the first one.
+        let comps: Vec<&SourceComponent> = source_list
+            .values()
+            .flat_map(|s| s.components.iter())
+            .collect();
+        let spread = comps
+            .iter()
+            .map(|comp| comp.radec.separation(comps[0].radec))
+            .fold(0.0, f64::max);
+        assert!(spread < 1.5f64.to_radians(), "spread {spread}");
+    }
+}
@@ -1,6 +1,8 @@
-pub(crate) mod error;
-pub(crate) mod read;
-pub(crate) mod types;
+pub mod error;
+pub mod generate;
+pub mod read;
+pub mod types;
+pub mod write;
 
 pub use error::*;
 use itertools::Itertools;
@@ -12,7 +14,7 @@ pub use types::*;
 #[derive(
     Debug, Clone, Copy, strum_macros::Display, strum_macros::EnumIter, strum_macros::EnumString,
 )]
-pub(crate) enum HyperdriveFileType {
+pub enum HyperdriveFileType {
     #[strum(serialize = "yaml")]
     Yaml,
 
@@ -21,5 +23,5 @@ pub(crate) enum HyperdriveFileType {
 }
 
 lazy_static::lazy_static! {
-    pub(crate) static ref HYPERDRIVE_SOURCE_LIST_FILE_TYPES_COMMA_SEPARATED: String = HyperdriveFileType::iter().join(", ");
+    pub static ref HYPERDRIVE_SOURCE_LIST_FILE_TYPES_COMMA_SEPARATED: String = HyperdriveFileType::iter().join(", ");
 }
@@ -3,20 +3,367 @@
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
 //! Code to read in hyperdrive source lists.
+//!
+//! Component positions can be given in degrees, or as sexagesimal strings
+//! (`"hh:mm:ss"` or `"10h20m30s"` for RA, `"dd:mm:ss"` or `"-27d30m00s"` for
+//! Dec). Instead of an RA, a component can have an hour angle `ha` [hours],
+//! which is converted to RA with the LST of the observation.
+//!
+//! Sky models too big to hold in memory can be read straight into a
+//! [`ComponentList`] with [`component_list_from_file`], which parses one
+//! source at a time and keeps only the components that survive a [`Veto`].
 
 use std::f64::consts::{FRAC_PI_2, TAU};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::str::FromStr;
 
+use marlu::sexagesimal::{
+    sexagesimal_colon_str_to_degrees, sexagesimal_dms_string_to_degrees,
+    sexagesimal_hms_string_to_degrees, SexagesimalError,
+};
 use marlu::RADec;
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde_yaml::{Mapping, Value};
 
-use crate::srclist::{error::ReadSourceListError, SourceList};
+use crate::srclist::{
+    Component, ComponentList, FluxDensityType, HyperdriveFileType, ReadSourceListError, SourceList,
+    Veto,
+};
+
+/// Read a yaml or json source list, depending on the file extension. Any hour
+/// angles are converted with `lst` [degrees].
+pub fn source_list_from_file<P: AsRef<Path>>(
+    path: P,
+    lst: Option<f64>,
+) -> Result<SourceList, ReadSourceListError> {
+    let file_type = file_type(path.as_ref())?;
+    let mut buf = io::BufReader::new(fs::File::open(path)?);
+    return match file_type {
+        HyperdriveFileType::Yaml => source_list_from_yaml(&mut buf, lst),
+        HyperdriveFileType::Json => source_list_from_json(&mut buf, lst),
+    };
+}
 
 /// Convert a yaml file to a [`SourceList`].
-pub(crate) fn source_list_from_yaml<T: std::io::BufRead>(
+pub fn source_list_from_yaml<T: std::io::BufRead>(
     buf: &mut T,
+    lst: Option<f64>,
 ) -> Result<SourceList, ReadSourceListError> {
-    let sl: SourceList = serde_yaml::from_reader(buf)?;
+    let mut value: Value = serde_yaml::from_reader(buf)?;
+    resolve_positions(&mut value, lst)?;
+    let sl: SourceList = serde_yaml::from_value(value)?;
+    check_positions(&sl)?;
+    return Ok(sl);
+}
+
+/// Convert a json file to a [`SourceList`].
+pub fn source_list_from_json<T: std::io::BufRead>(
+    buf: &mut T,
+    lst: Option<f64>,
+) -> Result<SourceList, ReadSourceListError> {
+    let mut value: Value = serde_json::from_reader(buf)?;
+    resolve_positions(&mut value, lst)?;
+    let sl: SourceList = serde_yaml::from_value(value)?;
+    check_positions(&sl)?;
+    return Ok(sl);
+}
+
+/// Read the components of a yaml or json source list that survive `veto`,
+/// depending on the file extension. Any hour angles are converted with `lst`
+/// [degrees].
+///
+/// Sources are parsed one at a time, so only the kept components are held in
+/// memory. A yaml source list is split at each top-level source name, which
+/// needs block style (as written by this crate and hyperdrive); a flow-style
+/// yaml mapping is still read, but all at once.
+pub fn component_list_from_file<P: AsRef<Path>>(
+    path: P,
+    lst: Option<f64>,
+    veto: &Veto,
+) -> Result<ComponentList, ReadSourceListError> {
+    let file_type = file_type(path.as_ref())?;
+    let buf = io::BufReader::new(fs::File::open(path)?);
+    let mut reader = ComponentReader {
+        lst,
+        veto,
+        components: vec![],
+        num_read: 0,
+        error: None,
+    };
+    match file_type {
+        HyperdriveFileType::Yaml => reader.read_yaml(buf)?,
+        HyperdriveFileType::Json => reader.read_json(buf)?,
+    }
+    return reader.finish();
+}
+
+/// Collects the components of a source list that survive a veto, one source
+/// at a time.
+struct ComponentReader<'a> {
+    lst: Option<f64>,
+
+    veto: &'a Veto,
+
+    /// The kept components, with the sources in reverse order (see
+    /// [`ComponentList::new`]) but the components of each reversed too
+    components: Vec<Component>,
+
+    /// Number of components read, including any vetoed
+    num_read: usize,
+
+    /// Why a json source list was abandoned part-way through, since serde
+    /// can only pass its own errors out of a [`Visitor`]
+    error: Option<ReadSourceListError>,
+}
+
+impl ComponentReader<'_> {
+    /// Add the kept components of `sources`, a mapping of source names to
+    /// their components.
+    fn add_sources(&mut self, mut sources: Value) -> Result<(), ReadSourceListError> {
+        // An empty document, or just comments
+        if sources.is_null() {
+            return Ok(());
+        }
+        resolve_positions(&mut sources, self.lst)?;
+        let sl: SourceList = serde_yaml::from_value(sources)?;
+        check_positions(&sl)?;
+
+        for (_, source) in sl {
+            self.num_read += source.components.len();
+            self.components.extend(
+                source
+                    .components
+                    .into_vec()
+                    .into_iter()
+                    .map(Component::from)
+                    .filter(|comp| self.veto.keeps(comp))
+                    .rev(),
+            );
+        }
+        return Ok(());
+    }
+
+    /// Read a block-style yaml source list one source at a time.
+    fn read_yaml<R: BufRead>(&mut self, buf: R) -> Result<(), ReadSourceListError> {
+        let mut chunk = String::new();
+        let mut has_source = false;
+        let mut flow = false;
+        for line in buf.lines() {
+            let line = line?;
+            if !has_source && line.starts_with('{') {
+                flow = true;
+            }
+            if !flow && starts_source(&line) {
+                if has_source {
+                    self.add_sources(serde_yaml::from_str(&chunk)?)?;
+                    chunk.clear();
+                }
+                has_source = true;
+            }
+            chunk.push_str(&line);
+            chunk.push('\n');
+        }
+        return self.add_sources(serde_yaml::from_str(&chunk)?);
+    }
+
+    /// Read a json source list one source at a time.
+    fn read_json<R: io::Read>(&mut self, buf: R) -> Result<(), ReadSourceListError> {
+        let mut deserializer = serde_json::Deserializer::from_reader(buf);
+        let result = deserializer.deserialize_map(&mut *self);
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        result?;
+        deserializer.end()?;
+        return Ok(());
+    }
+
+    fn finish(mut self) -> Result<ComponentList, ReadSourceListError> {
+        if self.num_read == 0 {
+            return Err(ReadSourceListError::NoSources);
+        }
+        if self.components.is_empty() {
+            return Err(ReadSourceListError::NoSourcesAfterVeto);
+        }
+        log::info!(
+            "Read {} components, {} after veto",
+            self.num_read,
+            self.components.len()
+        );
+        self.components.reverse();
+        return Ok(self.components.into_iter().collect());
+    }
+}
+
+impl<'de> Visitor<'de> for &mut ComponentReader<'_> {
+    type Value = ();
 
-    // Complain if we spot something wrong.
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of source names to their components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            let components: Value = map.next_value()?;
+            let source = Mapping::from_iter([(Value::from(name), components)]);
+            if let Err(e) = self.add_sources(Value::Mapping(source)) {
+                self.error = Some(e);
+                return Err(de::Error::custom("invalid source"));
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Does this line of a block-style yaml source list start a new source, i.e.
+/// is it a top-level key?
+fn starts_source(line: &str) -> bool {
+    let Some(first) = line.chars().next() else {
+        return false;
+    };
+    if first.is_whitespace() || matches!(first, '#' | '{' | '}' | '[' | ']') {
+        return false;
+    }
+    // Sequence items and document markers
+    return !(line == "-"
+        || line.starts_with("- ")
+        || line.starts_with("---")
+        || line.starts_with("..."));
+}
+
+/// Convert a sexagesimal RA (`"hh:mm:ss"` or `"XXhYYmZZs"`) to degrees.
+pub fn parse_ra(ra: &str) -> Result<f64, SexagesimalError> {
+    return Ok(parse_hours(ra)? * 15.0);
+}
+
+/// Convert a sexagesimal Dec (`"dd:mm:ss"` or `"XXdYYmZZs"`) to degrees.
+pub fn parse_dec(dec: &str) -> Result<f64, SexagesimalError> {
+    return parse_signed(dec, |dec| {
+        if dec.contains(':') {
+            return sexagesimal_colon_str_to_degrees(dec);
+        }
+        return sexagesimal_dms_string_to_degrees(dec);
+    });
+}
+
+/// Convert a sexagesimal time or angle in hours to hours.
+fn parse_hours(hours: &str) -> Result<f64, SexagesimalError> {
+    return parse_signed(hours, |hours| {
+        if hours.contains(':') {
+            // The conversion is the same as for degrees.
+            return sexagesimal_colon_str_to_degrees(hours);
+        }
+        return Ok(sexagesimal_hms_string_to_degrees(hours)? / 15.0);
+    });
+}
+
+/// Parse a sexagesimal string with `parse`, taking the sign from a leading
+/// `-`. marlu takes it from the first field instead, which loses it when that
+/// is zero (e.g. `"-00:30:00"`).
+fn parse_signed(
+    s: &str,
+    parse: impl Fn(&str) -> Result<f64, SexagesimalError>,
+) -> Result<f64, SexagesimalError> {
+    let s = s.trim();
+    return match s.strip_prefix('-') {
+        Some(abs) => Ok(-parse(abs)?),
+        None => parse(s),
+    };
+}
+
+/// Replace sexagesimal strings in each component's `ra` and `dec` with
+/// degrees, and any `ha` with the equivalent `ra`. Anything that isn't laid
+/// out like a source list is left for deserialisation to complain about.
+fn resolve_positions(value: &mut Value, lst: Option<f64>) -> Result<(), ReadSourceListError> {
+    let Some(sources) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+    let components = sources
+        .values_mut()
+        .filter_map(Value::as_sequence_mut)
+        .flatten()
+        .filter_map(Value::as_mapping_mut);
+
+    for comp in components {
+        if let Some(ha) = comp.remove("ha") {
+            let ha = match ha {
+                Value::String(ha) => parse_hours(&ha)?,
+                ha => serde_yaml::from_value(ha)?,
+            };
+            if !(-24.0 < ha && ha < 24.0) {
+                return Err(ReadSourceListError::InvalidHa(ha));
+            }
+            let lst = lst.ok_or(ReadSourceListError::NoLst)?;
+            let ra = (lst - ha * 15.0).rem_euclid(360.0);
+            comp.insert(Value::from("ra"), Value::from(ra));
+        }
+
+        if let Some(Value::String(ra)) = comp.get("ra") {
+            let ra = parse_ra(ra)?;
+            comp.insert(Value::from("ra"), Value::from(ra));
+        }
+        if let Some(Value::String(dec)) = comp.get("dec") {
+            let dec = parse_dec(dec)?;
+            comp.insert(Value::from("dec"), Value::from(dec));
+        }
+    }
+
+    return Ok(());
+}
+
+/// Work out the type of a source list from its file extension.
+pub fn file_type(path: &Path) -> Result<HyperdriveFileType, ReadSourceListError> {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default();
+    let ext = if ext == "yml" { "yaml" } else { ext };
+    return HyperdriveFileType::from_str(ext)
+        .map_err(|_| ReadSourceListError::UnknownFileType(ext.to_string()));
+}
+
+/// Check every component's flux densities, beyond what is needed to read the
+/// source list in: there must be at least one source, no NaNs, and no
+/// negative Stokes I sums.
+pub fn check_source_list(sl: &SourceList) -> Result<(), ReadSourceListError> {
+    if sl.is_empty() {
+        return Err(ReadSourceListError::NoSources);
+    }
+
+    for (name, source) in sl.iter() {
+        let mut sum_i = 0.0;
+        for comp in source.components.iter() {
+            let fds = match &comp.flux_type {
+                FluxDensityType::List(fds) => fds.to_vec(),
+                FluxDensityType::PowerLaw { fd, .. } => vec![*fd],
+                FluxDensityType::CurvedPowerLaw { fd, .. } => vec![*fd],
+            };
+            for fd in fds {
+                if [fd.freq, fd.i, fd.q, fd.u, fd.v].iter().any(|x| x.is_nan()) {
+                    return Err(ReadSourceListError::NaNsInComponent {
+                        source_name: name.clone(),
+                    });
+                }
+                sum_i += fd.i;
+            }
+        }
+        if sum_i < 0.0 {
+            return Err(ReadSourceListError::InvalidFluxDensitySum {
+                sum: sum_i,
+                stokes_comp: "I",
+                source_name: name.clone(),
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+/// Complain if any component's position is out of range.
+fn check_positions(sl: &SourceList) -> Result<(), ReadSourceListError> {
     for comp in sl.values().flat_map(|s| s.components.iter()) {
         let RADec { ra, dec } = comp.radec;
         if !(0.0..TAU).contains(&ra) {
@@ -26,6 +373,114 @@ pub(crate) fn source_list_from_yaml<T: std::io::BufRead>(
             return Err(ReadSourceListError::InvalidDec(dec.to_degrees()));
         }
     }
+    return Ok(());
+}
 
-    return Ok(sl);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srclist::{
+        write::source_list_to_file, ComponentType, FluxDensity, Fov, Source, SourceComponent,
+    };
+
+    #[test]
+    fn negative_sexagesimal_below_one_unit() {
+        assert_eq!(parse_dec("-00:30:00").unwrap(), -0.5);
+        assert_eq!(parse_dec("-00d30m00s").unwrap(), -0.5);
+        assert_eq!(parse_dec("-27:30:00").unwrap(), -27.5);
+        assert_eq!(parse_dec("00:30:00").unwrap(), 0.5);
+        assert_eq!(parse_hours("-00:30:00").unwrap(), -0.5);
+        assert_eq!(parse_hours("-00h30m00s").unwrap(), -0.5);
+        assert_eq!(parse_ra("10:30:00").unwrap(), 157.5);
+    }
+
+    #[test]
+    fn negative_positions_in_source_list() {
+        let yaml = "src:
+- ha: \"-00:30:00\"
+  dec: \"-00:30:00\"
+  comp_type: point
+  flux_type:
+    power_law:
+      si: -0.8
+      fd:
+        freq: 150000000.0
+        i: 1.0
+";
+        let sl = source_list_from_yaml(&mut yaml.as_bytes(), Some(10.0)).unwrap();
+        let radec = sl["src"].components[0].radec;
+        assert!((radec.ra.to_degrees() - 17.5).abs() < 1e-10);
+        assert!((radec.dec.to_degrees() + 0.5).abs() < 1e-10);
+    }
+
+    /// Sources at these (RA, Dec) [degrees] with these Stokes I [Jy]; the
+    /// last source has two components.
+    fn source_list() -> SourceList {
+        let comp = |ra: f64, dec: f64, i: f64| SourceComponent {
+            radec: RADec::from_degrees(ra, dec),
+            comp_type: ComponentType::Point,
+            flux_type: FluxDensityType::PowerLaw {
+                si: -0.8,
+                fd: FluxDensity {
+                    freq: 150e6,
+                    i,
+                    ..Default::default()
+                },
+            },
+        };
+        let source = |comps: Vec<SourceComponent>| Source {
+            components: comps.into_boxed_slice(),
+        };
+        return SourceList::from([
+            (
+                "bright_near".to_string(),
+                source(vec![comp(2.0, -27.0, 5.0)]),
+            ),
+            (
+                "faint_near".to_string(),
+                source(vec![comp(1.0, -26.0, 0.5)]),
+            ),
+            (
+                "bright_far".to_string(),
+                source(vec![comp(60.0, -27.0, 10.0)]),
+            ),
+            (
+                "double".to_string(),
+                source(vec![comp(359.0, -28.0, 2.0), comp(359.5, -28.0, 0.2)]),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn streaming_matches_reading_then_vetoing() {
+        let veto = Veto {
+            min_flux: Some(1.0),
+            fov: Some(Fov {
+                phase_centre: RADec::from_degrees(0.0, -27.0),
+                lambda: 2.0,
+                diameter: 4.4,
+            }),
+        };
+        for ext in ["yaml", "json"] {
+            let path =
+                std::env::temp_dir().join(format!("crb_{}_stream.{ext}", std::process::id()));
+            source_list_to_file(&path, &source_list()).unwrap();
+
+            let streamed = component_list_from_file(&path, None, &veto).unwrap();
+            let mut expected = ComponentList::new(source_list_from_file(&path, None).unwrap());
+            expected.veto(&veto);
+            assert_eq!(streamed.len(), 2, "{ext}");
+            assert_eq!(*streamed, *expected, "{ext}");
+
+            let everything = Veto {
+                min_flux: Some(100.0),
+                fov: None,
+            };
+            assert!(matches!(
+                component_list_from_file(&path, None, &everything),
+                Err(ReadSourceListError::NoSourcesAfterVeto)
+            ));
+            fs::remove_file(&path).unwrap();
+        }
+    }
 }
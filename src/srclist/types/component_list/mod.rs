@@ -2,67 +2,161 @@
 //! Follows the original python implementation of the CRB code a bit more.
 
 use super::{FluxDensity, FluxDensityType, SourceComponent, SourceList};
-use marlu::{RADec, LMN};
+use marlu::RADec;
 
 use std::ops::{Deref, DerefMut, Index, IndexMut};
 
-#[derive(Clone, Debug)]
-pub struct ComponentList(Vec<SourceComponent>);
+/// Upper limit on the number of k-means iterations when clustering.
+const MAX_KMEANS_ITERATIONS: usize = 100;
+
+/// A sky-model component reduced to what the CRB uses. Every component is
+/// treated as a point source, so its shape (and any shapelet coefficients)
+/// is dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+    pub radec: RADec,
+
+    pub flux_type: FluxDensityType,
+}
+
+impl Component {
+    /// Estimate the flux density of this component at a frequency.
+    pub fn estimate_at_freq(&self, freq_hz: f64) -> FluxDensity {
+        self.flux_type.estimate_at_freq(freq_hz)
+    }
+}
+
+impl From<SourceComponent> for Component {
+    fn from(comp: SourceComponent) -> Self {
+        return Self {
+            radec: comp.radec,
+            flux_type: comp.flux_type,
+        };
+    }
+}
+
+/// The field of view of a station of `diameter` [m] at wavelength `lambda`
+/// [m], centred on `phase_centre`.
+#[derive(Clone, Copy, Debug)]
+pub struct Fov {
+    pub phase_centre: RADec,
+
+    pub lambda: f64,
+
+    pub diameter: f64,
+}
+
+impl Fov {
+    /// Is `radec` within the field of view?
+    pub fn contains(&self, radec: RADec) -> bool {
+        let fov = self.lambda / self.diameter;
+        let lmn = radec.to_lmn(self.phase_centre);
+
+        return (lmn.l.powi(2) + lmn.m.powi(2)).sqrt() < (fov / 2.0f64).sin();
+    }
+}
+
+/// Which components to keep, for vetoing a list or while reading a source
+/// list. Nothing is vetoed by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Veto {
+    /// Drop components whose reference Stokes I is at most this [Jy], and any
+    /// with a list of flux densities
+    pub min_flux: Option<f64>,
+
+    /// Drop components outside this field of view
+    pub fov: Option<Fov>,
+}
+
+impl Veto {
+    /// Does `comp` survive the veto?
+    pub fn keeps(&self, comp: &Component) -> bool {
+        if let Some(noise) = self.min_flux {
+            let i = match comp.flux_type {
+                FluxDensityType::PowerLaw {
+                    fd: FluxDensity { i, .. },
+                    ..
+                } => i,
+                FluxDensityType::CurvedPowerLaw {
+                    fd: FluxDensity { i, .. },
+                    ..
+                } => i,
+                FluxDensityType::List { .. } => return false,
+            };
+            if i <= noise {
+                return false;
+            }
+        }
+        if let Some(fov) = self.fov {
+            return fov.contains(comp.radec);
+        }
+        return true;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ComponentList(Vec<Component>);
 
 impl ComponentList {
     /// Create a component list from an exisiting source_list
-    pub(crate) fn new(source_list: SourceList) -> ComponentList {
-        let mut component_list: Vec<SourceComponent> = vec![];
-
-        for comp in source_list
-            .iter()
+    pub fn new(source_list: SourceList) -> ComponentList {
+        return source_list
+            .into_iter()
             .rev()
-            .flat_map(|(_, src)| src.components.iter())
-        {
-            component_list.push(comp.clone());
-        }
+            .flat_map(|(_, src)| src.components.into_vec())
+            .map(Component::from)
+            .collect();
+    }
 
-        return ComponentList(component_list);
+    /// Drop the components that do not survive `veto`.
+    pub fn veto(&mut self, veto: &Veto) {
+        self.retain(|comp| veto.keeps(comp));
     }
 
     /// Veto sources by the minimum flux
-    pub(crate) fn veto_by_flux(&mut self, noise: f64) {
-        self.retain(|comp| match comp.flux_type {
-            FluxDensityType::PowerLaw {
-                fd: FluxDensity { i, .. },
-                ..
-            } => return i > noise,
-            FluxDensityType::CurvedPowerLaw {
-                fd: FluxDensity { i, .. },
-                ..
-            } => return i > noise,
-            FluxDensityType::List { .. } => return false,
+    pub fn veto_by_flux(&mut self, noise: f64) {
+        self.veto(&Veto {
+            min_flux: Some(noise),
+            fov: None,
         });
     }
 
     /// Veto sources by fov
-    pub(crate) fn veto_by_fov(&mut self, phase_centre: RADec, lambda: f64, D: f64) {
-        println!("fov: {}", (lambda / (D * 2.0f64)).sin());
-        return self.retain(|comp| {
-            let fov = lambda / D;
-            let lmn = comp.radec.to_lmn(phase_centre);
+    pub fn veto_by_fov(&mut self, phase_centre: RADec, lambda: f64, diameter: f64) {
+        log::debug!("fov: {}", (lambda / (diameter * 2.0f64)).sin());
+        let fov = Fov {
+            phase_centre,
+            lambda,
+            diameter,
+        };
+        return self.retain(|comp| fov.contains(comp.radec));
+    }
 
-            if (lmn.l.powi(2) + lmn.m.powi(2)).sqrt() < (fov / 2.0f64).sin() {
-                return true;
-            } else {
-                return false;
-            }
-        });
+    /// The components that survive `veto`, borrowed from the list so that
+    /// nothing is copied.
+    pub fn vetoed(&self, veto: &Veto) -> Vec<&Component> {
+        return self.iter().filter(|comp| veto.keeps(comp)).collect();
     }
 
-    pub(crate) fn slice_to_struct(&self, range: std::ops::Range<usize>) -> Self {
+    pub fn slice_to_struct(&self, range: std::ops::Range<usize>) -> Self {
         Self(self.0[range].to_vec())
     }
+
+    /// The components at these indices, in that order.
+    pub fn select(&self, indices: &[usize]) -> Self {
+        return Self(indices.iter().map(|&i| self.0[i].clone()).collect());
+    }
+}
+
+impl FromIterator<Component> for ComponentList {
+    fn from_iter<I: IntoIterator<Item = Component>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
 }
 
 // Need these to expose the iter() functionality of Vec
 impl Deref for ComponentList {
-    type Target = Vec<SourceComponent>;
+    type Target = Vec<Component>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -76,7 +170,7 @@ impl DerefMut for ComponentList {
 }
 
 impl Index<std::ops::Range<usize>> for ComponentList {
-    type Output = [SourceComponent];
+    type Output = [Component];
 
     fn index(&self, index: std::ops::Range<usize>) -> &Self::Output {
         &self.0[index]
@@ -88,3 +182,76 @@ impl IndexMut<std::ops::Range<usize>> for ComponentList {
         &mut self.0[index]
     }
 }
+
+/// Put every component into a group of its own.
+pub fn singletons<'a>(components: &[&'a Component]) -> Vec<Vec<&'a Component>> {
+    return components.iter().map(|&comp| vec![comp]).collect();
+}
+
+/// Group the components into (at most) `num_clusters` calibration
+/// directions with Stokes I flux-weighted k-means in (l, m). The brightest
+/// components seed the cluster centres, so the result is deterministic.
+/// Empty clusters are dropped.
+pub fn cluster_components<'a>(
+    components: &[&'a Component],
+    num_clusters: usize,
+    phase_centre: RADec,
+    freq: f64,
+) -> Vec<Vec<&'a Component>> {
+    let points: Vec<(f64, f64, f64)> = components
+        .iter()
+        .map(|comp| {
+            let lmn = comp.radec.to_lmn(phase_centre);
+            (lmn.l, lmn.m, comp.estimate_at_freq(freq).i.abs())
+        })
+        .collect();
+
+    let mut by_flux: Vec<usize> = (0..points.len()).collect();
+    by_flux.sort_by(|&a, &b| points[b].2.total_cmp(&points[a].2));
+    let mut centres: Vec<(f64, f64)> = by_flux
+        .iter()
+        .take(num_clusters)
+        .map(|&i| (points[i].0, points[i].1))
+        .collect();
+
+    let mut labels = vec![usize::MAX; points.len()];
+    for _ in 0..MAX_KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (label, (l, m, _)) in labels.iter_mut().zip(points.iter()) {
+            let nearest = centres
+                .iter()
+                .map(|(cl, cm)| (l - cl).powi(2) + (m - cm).powi(2))
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(0, |(k, _)| k);
+            if nearest != *label {
+                *label = nearest;
+                changed = true;
+            }
+        }
+
+        let mut sums = vec![(0.0, 0.0, 0.0); centres.len()];
+        for (&label, (l, m, flux)) in labels.iter().zip(points.iter()) {
+            sums[label].0 += flux * l;
+            sums[label].1 += flux * m;
+            sums[label].2 += flux;
+        }
+        for (centre, (l, m, flux)) in centres.iter_mut().zip(sums) {
+            if flux > 0.0 {
+                *centre = (l / flux, m / flux);
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut clusters = vec![vec![]; centres.len()];
+    for (&label, &comp) in labels.iter().zip(components) {
+        clusters[label].push(comp);
+    }
+    clusters.retain(|cluster| !cluster.is_empty());
+
+    return clusters;
+}
@@ -6,11 +6,11 @@
 //! - ra: ...
 //!   dec: ...
 //!   comp_type: ...
-//!     ...
+//!   ...
 //!   flux_type: ...
-//!     ...
+//!   ...
 
-use super::{FluxDensity, FluxDensityType, SourceList};
+use super::{FluxDensity, FluxDensityType};
 use marlu::RADec;
 use serde::{Deserialize, Serialize};
 
@@ -26,22 +26,22 @@ pub struct SourceComponent {
 
 impl SourceComponent {
     /// Estimate the flux density of this component at a frequency.
-    pub(crate) fn estimate_at_freq(&self, freq_hz: f64) -> FluxDensity {
+    pub fn estimate_at_freq(&self, freq_hz: f64) -> FluxDensity {
         self.flux_type.estimate_at_freq(freq_hz)
     }
 
     /// Is this component a point source?
-    pub(crate) fn is_point(&self) -> bool {
+    pub fn is_point(&self) -> bool {
         self.comp_type.is_point()
     }
 
     /// Is this component a gaussian source?
-    pub(crate) fn is_gaussian(&self) -> bool {
+    pub fn is_gaussian(&self) -> bool {
         self.comp_type.is_gaussian()
     }
 
     /// Is this component a shapelet source?
-    pub(crate) fn is_shapelet(&self) -> bool {
+    pub fn is_shapelet(&self) -> bool {
         self.comp_type.is_shapelet()
     }
 }
@@ -69,15 +69,15 @@ pub enum ComponentType {
 }
 
 impl ComponentType {
-    pub(crate) fn is_point(&self) -> bool {
+    pub fn is_point(&self) -> bool {
         return matches!(self, Self::Point);
     }
 
-    pub(crate) fn is_gaussian(&self) -> bool {
+    pub fn is_gaussian(&self) -> bool {
         return matches!(self, Self::Gaussian { .. });
     }
 
-    pub(crate) fn is_shapelet(&self) -> bool {
+    pub fn is_shapelet(&self) -> bool {
         return matches!(self, Self::Shapelet { .. });
     }
 }
@@ -9,7 +9,7 @@
 //!     q: ...
 //!     u: ...
 //!
-pub(crate) const DEFAULT_SPEC_INDEX: f64 = -0.8;
+pub const DEFAULT_SPEC_INDEX: f64 = -0.8;
 use serde::{Deserialize, Serialize};
 // vec1 ensures there is at least one item in the vector
 use vec1::Vec1;
@@ -100,7 +100,7 @@ impl FluxDensityType {
     /// read by hyperdrive). The estimated flux density is based off of the
     /// Stokes I component, so any other Stokes parameters may be poorly
     /// estimated.
-    pub(crate) fn estimate_at_freq(&self, freq_hz: f64) -> FluxDensity {
+    pub fn estimate_at_freq(&self, freq_hz: f64) -> FluxDensity {
         match self {
             FluxDensityType::PowerLaw { si, fd } => {
                 let ratio = calc_flux_ratio(freq_hz, fd.freq, *si);
@@ -205,6 +205,6 @@ impl FluxDensityType {
 }
 
 /// Given a spectral index, determine the flux-density ratio of two frequencies.
-pub(crate) fn calc_flux_ratio(desired_freq_hz: f64, cat_freq_hz: f64, spec_index: f64) -> f64 {
+pub fn calc_flux_ratio(desired_freq_hz: f64, cat_freq_hz: f64, spec_index: f64) -> f64 {
     (desired_freq_hz / cat_freq_hz).powf(spec_index)
 }
@@ -10,7 +10,7 @@ use super::*;
 /// We could instead define a variable SourceList of type IndexMap
 /// but then we would have to impl for IndexMap. This method means
 /// we can impl for SourceList.
-
+///
 /// The transparent attribute means the whole yaml file is the "value".
 /// If it was not here, then the whole yaml file would need a key "source_list"
 /// above everything.
@@ -21,7 +21,7 @@ use super::*;
 pub struct SourceList(IndexMap<String, Source>);
 
 impl SourceList {
-    pub(crate) fn new() -> Self {
+    pub fn new() -> Self {
         return Self::default();
     }
 }
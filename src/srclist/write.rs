@@ -0,0 +1,45 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Code to write out hyperdrive source lists.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::srclist::{read::file_type, HyperdriveFileType, SourceList, WriteSourceListError};
+
+/// Write a yaml or json source list, depending on the file extension.
+pub fn source_list_to_file<P: AsRef<Path>>(
+    path: P,
+    sl: &SourceList,
+) -> Result<(), WriteSourceListError> {
+    let file_type = file_type(path.as_ref())
+        .map_err(|_| WriteSourceListError::UnknownFileType(path.as_ref().display().to_string()))?;
+    let mut buf = io::BufWriter::new(fs::File::create(path)?);
+    return match file_type {
+        HyperdriveFileType::Yaml => source_list_to_yaml(&mut buf, sl),
+        HyperdriveFileType::Json => source_list_to_json(&mut buf, sl),
+    };
+}
+
+/// Write a [`SourceList`] to a yaml file.
+pub fn source_list_to_yaml<T: std::io::Write>(
+    buf: &mut T,
+    sl: &SourceList,
+) -> Result<(), WriteSourceListError> {
+    serde_yaml::to_writer(&mut *buf, sl)?;
+    buf.flush()?;
+    return Ok(());
+}
+
+/// Write a [`SourceList`] to a json file.
+pub fn source_list_to_json<T: std::io::Write>(
+    buf: &mut T,
+    sl: &SourceList,
+) -> Result<(), WriteSourceListError> {
+    serde_json::to_writer_pretty(&mut *buf, sl)?;
+    buf.flush()?;
+    return Ok(());
+}